@@ -0,0 +1,212 @@
+//! `rainbase` - play at a rainbase table from the terminal.
+//!
+//! ```text
+//! rainbase keygen --contract rainbase.testnet --account ~/.near-credentials/testnet/alice.testnet.json
+//! rainbase create --buy-in 1000 ...    # prints the table's id, e.g. 4821
+//! rainbase join 4821 --buy-in 1000 ...
+//! rainbase start 4821 ...              # once everyone has joined
+//! rainbase play 4821 ...
+//! ```
+//!
+//! `--contract` and `--account` can also be set with `RAINBASE_CONTRACT` and `RAINBASE_ACCOUNT`.
+//! `rainbase sandbox` deploys the contract to a local sandbox and seats you at a table with bots, for trying it out.
+
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{bail, ensure, Context, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use workspaces::Account;
+use rainbase_client::{GameId, GameKeys, SavedKeys, TableClient, WorkspacesBackend};
+
+mod play;
+mod sandbox;
+
+/// where `cargo build` puts the contract, relative to the repo root
+const DEFAULT_WASM: &str = "contract/target/wasm32-unknown-unknown/release/rainbase_contract.wasm";
+
+#[derive(Parser)]
+#[command(name = "rainbase", about = "Play poker at a rainbase table")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// generates a game key and saves it. Anyone who gets hold of the file can see your cards
+    Keygen {
+        #[command(flatten)]
+        net: NetworkArgs,
+
+        /// overwrite an existing key file. Don't do this while you're seated somewhere - you won't be able to play your hands
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// opens a table and buys in
+    Create {
+        #[command(flatten)]
+        net: NetworkArgs,
+
+        /// chips to buy in with
+        #[arg(long)]
+        buy_in: u128,
+
+        /// the table's config as JSON, e.g. '{"small_blind": "25", "big_blind": "50"}'. Defaults to the contract's
+        #[arg(long)]
+        config: Option<String>,
+    },
+
+    /// joins a table that hasn't started yet
+    Join {
+        #[command(flatten)]
+        net: NetworkArgs,
+
+        /// the table's id, e.g. 0427
+        game_id: String,
+
+        /// chips to buy in with
+        #[arg(long)]
+        buy_in: u128,
+    },
+
+    /// starts a table you created, once everyone has joined
+    Start {
+        #[command(flatten)]
+        net: NetworkArgs,
+
+        /// the table's id, e.g. 0427
+        game_id: String,
+    },
+
+    /// sits down at a table you've joined. Shuffling, dealing and revealing happen automatically - you're only asked how to bet
+    Play {
+        #[command(flatten)]
+        net: NetworkArgs,
+
+        /// the table's id, e.g. 0427
+        game_id: String,
+    },
+
+    /// deploys the contract to a local sandbox and plays a game against bots
+    Sandbox {
+        /// the compiled contract
+        #[arg(long, default_value = DEFAULT_WASM)]
+        wasm: PathBuf,
+
+        /// how many bots to play against. They check when they can, and call otherwise
+        #[arg(long, default_value_t = 2)]
+        bots: usize,
+
+        /// chips everyone buys in with
+        #[arg(long, default_value_t = 1000)]
+        buy_in: u128,
+    },
+}
+
+#[derive(Args)]
+struct NetworkArgs {
+    /// the network the contract is deployed on
+    #[arg(long, value_enum, default_value_t = Network::Testnet)]
+    network: Network,
+
+    /// the account the contract is deployed to
+    #[arg(long, env = "RAINBASE_CONTRACT")]
+    contract: String,
+
+    /// near-cli credentials for the account to play as, e.g. ~/.near-credentials/testnet/you.testnet.json
+    #[arg(long, env = "RAINBASE_ACCOUNT")]
+    account: PathBuf,
+
+    /// where your game key is saved
+    #[arg(long, default_value = "rainbase-keys.json")]
+    keys: PathBuf,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Network {
+    Testnet,
+    Mainnet,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    match Cli::parse().command {
+        Command::Keygen { net, force } => {
+            ensure!(force || !net.keys.exists(), "{} already exists - pass --force to replace it", net.keys.display());
+            let client = TableClient::new(connect(&net).await?).await?;
+            fs::write(&net.keys, serde_json::to_string_pretty(&client.keys().to_saved()?)?)?;
+            println!("saved your game key to {}", net.keys.display());
+        },
+        Command::Create { net, buy_in, config } => {
+            let config = config.map(|config| serde_json::from_str(&config)).transpose().context("--config isn't valid JSON")?;
+            let mut client = load_client(&net).await?;
+            let yocto = buy_in * client.yocto_per_chip().await?;
+            let game_id = client.create(yocto, config).await?;
+            println!("opened table {} - once everyone has joined, start it with `rainbase start {}`", format_game_id(game_id), format_game_id(game_id));
+        },
+        Command::Join { net, game_id, buy_in } => {
+            let game_id = parse_game_id(&game_id)?;
+            let mut client = load_client(&net).await?;
+            let yocto = buy_in * client.yocto_per_chip().await?;
+            client.join(game_id, yocto).await?;
+            println!("joined table {} - waiting for the creator to start it", format_game_id(game_id));
+        },
+        Command::Start { net, game_id } => {
+            let mut client = load_client(&net).await?;
+            client.set_game(parse_game_id(&game_id)?);
+            client.start().await?;
+            println!("started - sit down with `rainbase play {}`", game_id);
+        },
+        Command::Play { net, game_id } => {
+            let mut client = load_client(&net).await?;
+            client.set_game(parse_game_id(&game_id)?);
+            play::play(&mut client, &mut []).await?;
+        },
+        Command::Sandbox { wasm, bots, buy_in } => sandbox::run(&wasm, bots, buy_in).await?,
+    }
+    Ok(())
+}
+
+async fn connect(net: &NetworkArgs) -> Result<WorkspacesBackend> {
+    let account = match net.network {
+        Network::Testnet => Account::from_file(&net.account, &workspaces::testnet().await?)?,
+        Network::Mainnet => Account::from_file(&net.account, &workspaces::mainnet().await?)?,
+    };
+    Ok(WorkspacesBackend::new(account, net.contract.parse()?))
+}
+
+/// a client playing with the game key saved by `rainbase keygen`
+async fn load_client(net: &NetworkArgs) -> Result<TableClient<WorkspacesBackend>> {
+    let saved = fs::read_to_string(&net.keys).with_context(|| format!("couldn't read {} - run `rainbase keygen` first", net.keys.display()))?;
+    let saved: SavedKeys = serde_json::from_str(&saved)?;
+    TableClient::with_keys(connect(net).await?, GameKeys::from_saved(&saved)?).await
+}
+
+/// game ids are four digits, written the way the contract generates them, e.g. `0427`
+fn parse_game_id(s: &str) -> Result<GameId> {
+    let digits: Vec<u8> = s.chars().map(|c| c.to_digit(10).map(|d| d as u8)).collect::<Option<_>>().unwrap_or_default();
+    match digits.try_into() {
+        Ok(game_id) => Ok(game_id),
+        Err(_) => bail!("{:?} isn't a game id - they're four digits, e.g. 0427", s),
+    }
+}
+
+fn format_game_id(game_id: GameId) -> String {
+    game_id.iter().map(|d| d.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn game_ids() {
+        assert_eq!(parse_game_id("0427").unwrap(), [0, 4, 2, 7]);
+        assert_eq!(format_game_id([0, 4, 2, 7]), "0427");
+        assert!(parse_game_id("427").is_err());
+        assert!(parse_game_id("04a7").is_err());
+        assert!(parse_game_id("04271").is_err());
+    }
+}
@@ -0,0 +1,227 @@
+use std::io::Write;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use tokio::io::{AsyncBufReadExt, BufReader, Lines, Stdin};
+use tokio::time::sleep;
+use rainbase_client::{Action, Backend, Card, Chips, ContractError, GameView, Phase, TableClient};
+
+/// how long to wait before polling the table again when nobody is waiting on us
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// what a player can type when it's their turn to bet
+#[derive(Debug, PartialEq, Eq)]
+enum Input {
+    Act(Action),
+    Leave,
+}
+
+/// what this player has seen of the current hand, so it's only unmasked once
+#[derive(Default)]
+struct Hand {
+    hole_cards: Option<[Card; 2]>,
+    board: Vec<Card>,
+}
+
+/// Plays at `me`'s table until the game is over or `me` has left it. Shuffling, dealing and revealing are done automatically, and the
+/// player is prompted on stdin whenever it's their turn to bet. `bots` are played here too, checking when they can and calling otherwise -
+/// anyone else at the table has to be running their own client
+pub async fn play<B: Backend>(me: &mut TableClient<B>, bots: &mut [TableClient<B>]) -> Result<()> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut seated = false;
+    let mut hand = Hand::default();
+    let mut shown = String::new();
+
+    loop {
+        let game = match me.try_game().await? {
+            Some(game) => game,
+            None if me.lobby_players().await?.is_some() => {
+                show(&mut shown, "waiting for the creator to start the game...".to_string());
+                sleep(POLL_INTERVAL).await;
+                continue;
+            },
+            None if seated => {
+                println!("the game is over");
+                return Ok(());
+            },
+            None => bail!("there's no table with that id, or its game is already over"),
+        };
+
+        let seat = match me.seat(&game) {
+            Some(seat) => seat,
+            None if seated => {
+                println!("you've left the table");
+                return Ok(());
+            },
+            None => {
+                show(&mut shown, "waiting for a seat...".to_string());
+                sleep(POLL_INTERVAL).await;
+                continue;
+            },
+        };
+        seated = true;
+
+        if matches!(game.phase, Phase::Shuffle | Phase::Deal) {
+            hand = Hand::default();
+        } else {
+            if hand.hole_cards.is_none() && game.dealt_in[seat] {
+                hand.hole_cards = me.peek_hole_cards().await?;
+            }
+            if hand.board.len() != game.num_community_cards() {
+                hand.board = me.community_cards().await?;
+            }
+        }
+        show(&mut shown, render(&game, seat, &hand));
+
+        let mut busy = me.perform_duties().await?;
+        for bot in bots.iter_mut() {
+            busy |= play_bot(bot).await?;
+        }
+        if busy {
+            continue;
+        }
+
+        if game.phase.is_betting() && game.turn == seat {
+            let input = match prompt(&mut lines, &game, seat).await? {
+                Some(input) => input,
+                None => return Ok(()),
+            };
+            let result = match input {
+                Input::Act(action) => me.act(action).await,
+                Input::Leave => me.leave().await.map(|_| println!("you'll leave once this hand is over")),
+            };
+            // the contract's the judge of what's legal - tell the player what was wrong and ask again
+            if let Err(err) = result {
+                match err.downcast_ref::<ContractError>() {
+                    Some(err) => println!("{}", err.message),
+                    None => return Err(err),
+                }
+            }
+            continue;
+        }
+
+        sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// prints `text` unless it's what was printed last
+fn show(shown: &mut String, text: String) {
+    if *shown != text {
+        println!("{}", text);
+        *shown = text;
+    }
+}
+
+fn render(game: &GameView, me: usize, hand: &Hand) -> String {
+    let mut out = format!("\n{:?} - pot {}", game.phase, game.pot());
+    if !hand.board.is_empty() {
+        out += &format!(" - board {}", format_cards(&hand.board));
+    }
+
+    for (seat, account_id) in game.player_account_ids.iter().enumerate() {
+        let turn = if game.phase.is_betting() && game.turn == seat { ">" } else { " " };
+        let dealer = if game.dealer == seat { "(D)" } else { "   " };
+        let status = if !game.dealt_in[seat] {
+            "sitting out".to_string()
+        } else if game.bets[seat].is_folded() {
+            "folded".to_string()
+        } else {
+            format!("in for {}", game.bets[seat].amount())
+        };
+        out += &format!("\n{} {} {:<32} {:>10}  {}", turn, dealer, account_id, game.balances[seat], status);
+
+        if seat == me {
+            out += "  (you)";
+            if let Some(cards) = &hand.hole_cards {
+                out += &format!(" {}", format_cards(cards));
+            }
+        }
+    }
+    out
+}
+
+fn format_cards(cards: &[Card]) -> String {
+    cards.iter().map(|card| card.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+/// asks the player what to do until they type something that makes sense. `None` if stdin is closed
+async fn prompt(lines: &mut Lines<BufReader<Stdin>>, game: &GameView, seat: usize) -> Result<Option<Input>> {
+    let to_call = game.current_bet.0 - game.street_bets[seat].0;
+    loop {
+        if to_call == 0 {
+            print!("your turn - check, bet <chips>, all-in, fold or leave: ");
+        } else {
+            let min_raise = game.current_bet.0 + game.min_raise.0;
+            print!("your turn, {} to call - call, raise <to at least {}>, all-in, fold or leave: ", to_call, min_raise);
+        }
+        std::io::stdout().flush()?;
+
+        let line = match lines.next_line().await? {
+            Some(line) => line,
+            None => return Ok(None),
+        };
+        match parse_input(&line) {
+            Ok(input) => return Ok(Some(input)),
+            Err(err) => println!("{}", err),
+        }
+    }
+}
+
+fn parse_input(line: &str) -> Result<Input> {
+    let line = line.trim().to_lowercase();
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let input = match words.as_slice() {
+        ["check" | "k"] => Input::Act(Action::Check),
+        ["call" | "c"] => Input::Act(Action::Call),
+        ["bet" | "b", chips] => Input::Act(Action::Bet(parse_chips(chips)?)),
+        ["raise" | "r", chips] | ["raise" | "r", "to", chips] => Input::Act(Action::Raise { to: parse_chips(chips)? }),
+        ["all-in" | "allin" | "a"] => Input::Act(Action::AllIn),
+        ["fold" | "f"] => Input::Act(Action::Fold),
+        ["leave"] => Input::Leave,
+        _ => bail!("didn't understand {:?}", line),
+    };
+    Ok(input)
+}
+
+fn parse_chips(chips: &str) -> Result<Chips> {
+    chips.parse().map(Chips).map_err(|_| anyhow!("{:?} isn't a number of chips", chips))
+}
+
+/// does a bot's duties, or bets for it if it's its turn. Returns whether it did anything
+async fn play_bot<B: Backend>(bot: &mut TableClient<B>) -> Result<bool> {
+    if bot.try_game().await?.is_none() {
+        return Ok(false);
+    }
+    if bot.perform_duties().await? {
+        return Ok(true);
+    }
+
+    let game = bot.game().await?;
+    let seat = match bot.seat(&game) {
+        Some(seat) if game.phase.is_betting() && game.turn == seat => seat,
+        _ => return Ok(false),
+    };
+    let (action, verb) = if game.street_bets[seat] == game.current_bet { (Action::Check, "checks") } else { (Action::Call, "calls") };
+    bot.act(action).await?;
+    println!("{} {}", game.player_account_ids[seat], verb);
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inputs() {
+        assert_eq!(parse_input("check").unwrap(), Input::Act(Action::Check));
+        assert_eq!(parse_input(" C ").unwrap(), Input::Act(Action::Call));
+        assert_eq!(parse_input("bet 50").unwrap(), Input::Act(Action::Bet(Chips(50))));
+        assert_eq!(parse_input("raise to 200").unwrap(), Input::Act(Action::Raise { to: Chips(200) }));
+        assert_eq!(parse_input("r 200").unwrap(), Input::Act(Action::Raise { to: Chips(200) }));
+        assert_eq!(parse_input("all-in").unwrap(), Input::Act(Action::AllIn));
+        assert_eq!(parse_input("leave").unwrap(), Input::Leave);
+        assert!(parse_input("bet").is_err());
+        assert!(parse_input("bet lots").is_err());
+        assert!(parse_input("shove").is_err());
+    }
+}
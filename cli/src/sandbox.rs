@@ -0,0 +1,54 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, ensure, Context, Result};
+use rand::thread_rng;
+use serde_json::json;
+use barnett_smart_card_protocol::{
+    BarnettSmartProtocol,
+    discrete_log_cards::{BnCardProtocol, BnParamsBuf},
+};
+use rainbase_client::{TableClient, WorkspacesBackend};
+
+use crate::{format_game_id, play};
+
+/// the trusted setup's shape - `M * N` has to be the size of the deck
+const M: usize = 2;
+const N: usize = 26;
+
+/// gas for `init`, which deserializes and stores the trusted setup
+const INIT_GAS: u64 = 300_000_000_000_000;
+
+/// starts a sandbox, deploys and initializes the contract in it, seats you and `num_bots` bots at a fresh table, and plays until the game is over
+pub async fn run(wasm: &Path, num_bots: usize, buy_in: u128) -> Result<()> {
+    ensure!(num_bots >= 1, "you need at least one bot to play against");
+    let wasm = fs::read(wasm).with_context(|| format!("couldn't read {} - build the contract first", wasm.display()))?;
+
+    println!("starting the sandbox...");
+    let worker = workspaces::sandbox().await?;
+    let contract = worker.dev_deploy(&wasm).await?;
+
+    let params = BnCardProtocol::setup(&mut thread_rng(), M, N).map_err(|err| anyhow!("trusted setup failed: {:?}", err))?;
+    let params = BnParamsBuf::serialize(params).map_err(|err| anyhow!("failed to serialize trusted setup params: {:?}", err))?;
+    contract.call("init")
+        .gas(INIT_GAS)
+        .args_json(json!({ "trusted_setup_params": params }))
+        .transact()
+        .await?
+        .into_result()?;
+
+    let mut me = TableClient::new(WorkspacesBackend::new(worker.dev_create_account().await?, contract.id().clone())).await?;
+    let yocto = buy_in * me.yocto_per_chip().await?;
+    let game_id = me.create(yocto, None).await?;
+
+    let mut bots = Vec::new();
+    for _ in 0..num_bots {
+        let mut bot = TableClient::new(WorkspacesBackend::new(worker.dev_create_account().await?, contract.id().clone())).await?;
+        bot.join(game_id, yocto).await?;
+        bots.push(bot);
+    }
+
+    me.start().await?;
+    println!("table {} is open, with {} bot(s)", format_game_id(game_id), num_bots);
+    play::play(&mut me, &mut bots).await
+}
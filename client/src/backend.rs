@@ -0,0 +1,77 @@
+use anyhow::bail;
+use async_trait::async_trait;
+use serde_json::Value;
+use workspaces::{Account, AccountId};
+
+use crate::error::ContractError;
+
+/// gas attached to every call. The shuffle verification needs most of the 300 TGas a transaction can have
+const GAS_PER_CALL: u64 = 300_000_000_000_000;
+
+/// How a `TableClient` talks to the contract. Calls are signed by the player the client is playing for.
+///
+/// `WorkspacesBackend` covers the sandbox and, through `workspaces::testnet()` / `workspaces::mainnet()`, the live networks.
+/// Anything else that can sign function calls (a wallet, a JSON-RPC client with a key file) just has to implement this.
+#[async_trait]
+pub trait Backend: Send + Sync {
+    /// the account calls are signed by - this is the account the player's game key is bound to
+    fn account_id(&self) -> String;
+
+    /// calls `method` with `args`, attaching `deposit` yoctoNEAR. Returns whatever the method returned, or `Value::Null` if it returned nothing.
+    /// If the contract rejects the call, the error is a `ContractError`
+    async fn call(&self, method: &str, args: Value, deposit: u128) -> anyhow::Result<Value>;
+
+    async fn view(&self, method: &str, args: Value) -> anyhow::Result<Value>;
+}
+
+pub struct WorkspacesBackend {
+    account: Account,
+    contract_id: AccountId,
+}
+
+impl WorkspacesBackend {
+    pub fn new(account: Account, contract_id: AccountId) -> Self {
+        Self { account, contract_id }
+    }
+
+    pub fn account(&self) -> &Account {
+        &self.account
+    }
+}
+
+#[async_trait]
+impl Backend for WorkspacesBackend {
+    fn account_id(&self) -> String {
+        self.account.id().to_string()
+    }
+
+    async fn call(&self, method: &str, args: Value, deposit: u128) -> anyhow::Result<Value> {
+        let outcome = self.account.call(&self.contract_id, method)
+            .gas(GAS_PER_CALL)
+            .deposit(deposit)
+            .args_json(args)
+            .transact()
+            .await?;
+
+        if outcome.is_failure() {
+            let failures = format!("{:?}", outcome.failures());
+            match ContractError::from_failure(&failures) {
+                Some(err) => return Err(err.into()),
+                None => bail!("{} failed: {}", method, failures),
+            }
+        }
+
+        let bytes = outcome.into_result()?.raw_bytes()?;
+        if bytes.is_empty() {
+            return Ok(Value::Null);
+        }
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    async fn view(&self, method: &str, args: Value) -> anyhow::Result<Value> {
+        let result = self.account
+            .view(&self.contract_id, method, args.to_string().into_bytes())
+            .await?;
+        Ok(result.json()?)
+    }
+}
@@ -0,0 +1,46 @@
+use anyhow::{anyhow, Result};
+use poker::{Card, Rank, Suit};
+use barnett_smart_card_protocol::discrete_log_cards::{get_card_elems_buf, BnCard, BnCardBuf};
+
+pub const DECK_SIZE: usize = 52;
+
+const SUITS: [Suit; 4] = [Suit::Spades, Suit::Hearts, Suit::Diamonds, Suit::Clubs];
+const RANKS: [Rank; 13] = [
+    Rank::Two, Rank::Three, Rank::Four, Rank::Five, Rank::Six, Rank::Seven, Rank::Eight, Rank::Nine, Rank::Ten, Rank::Jack, Rank::Queen, Rank::King, Rank::Ace
+];
+
+/// the card the contract maps the `idx`th canonical card value to
+pub fn card_from_index(idx: usize) -> Card {
+    Card::new(RANKS[idx % 13], SUITS[idx / 13])
+}
+
+/// Turns unmasked card values back into cards, the same way the contract does at showdown
+pub struct CardDecoder {
+    values: Vec<BnCardBuf>,
+}
+
+impl CardDecoder {
+    pub fn new() -> Result<Self> {
+        let values = get_card_elems_buf(DECK_SIZE).map_err(|err| anyhow!("failed to get card values: {:?}", err))?;
+        Ok(Self { values })
+    }
+
+    pub fn decode(&self, card: BnCard) -> Result<Card> {
+        let card = BnCardBuf::serialize(card).map_err(|err| anyhow!("failed to serialize card: {:?}", err))?;
+        let idx = self.values.iter().position(|value| value.buf == card.buf).ok_or_else(|| anyhow!("unmasked value isn't a card"))?;
+        Ok(card_from_index(idx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_mapping() {
+        assert_eq!(card_from_index(0), Card::new(Rank::Two, Suit::Spades));
+        assert_eq!(card_from_index(12), Card::new(Rank::Ace, Suit::Spades));
+        assert_eq!(card_from_index(13), Card::new(Rank::Two, Suit::Hearts));
+        assert_eq!(card_from_index(51), Card::new(Rank::Ace, Suit::Clubs));
+    }
+}
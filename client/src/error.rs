@@ -0,0 +1,52 @@
+use std::fmt;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+/// prefix the contract puts in front of the JSON for its errors
+const ERROR_PREFIX: &str = "ERR_JSON:";
+
+/// An error the contract returned, e.g. `NOT_YOUR_TURN`. `code` is stable, so match on that rather than the message.
+/// Backends return these inside `anyhow::Error` - get them back out with `err.downcast_ref::<ContractError>()`
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ContractError {
+    pub code: String,
+    #[serde(default)]
+    pub data: Value,
+    pub message: String,
+}
+
+impl ContractError {
+    /// finds and decodes the contract error in the description of a failed transaction, whatever the backend wraps it in
+    pub fn from_failure(failure: &str) -> Option<Self> {
+        // the JSON usually comes back escaped inside the backend's own error message
+        let failure = failure.replace("\\\"", "\"");
+        let (_, rest) = failure.split_once(ERROR_PREFIX)?;
+        let mut json = serde_json::Deserializer::from_str(rest).into_iter::<ContractError>();
+        json.next()?.ok()
+    }
+}
+
+impl fmt::Display for ContractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for ContractError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_escaped_errors() {
+        let failure = r#"[ExecutionOutcome { status: Failure(ActionError(ActionError { kind: FunctionCallError(ExecutionError("Smart contract panicked: ERR_JSON:{\"code\":\"NOT_YOUR_TURN\",\"data\":null,\"message\":\"it is not your turn\"}")) })) }]"#;
+        assert_eq!(ContractError::from_failure(failure), Some(ContractError {
+            code: "NOT_YOUR_TURN".to_string(),
+            data: Value::Null,
+            message: "it is not your turn".to_string(),
+        }));
+        assert_eq!(ContractError::from_failure("Exceeded the prepaid gas"), None);
+    }
+}
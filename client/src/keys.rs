@@ -0,0 +1,54 @@
+use anyhow::{anyhow, Result};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use barnett_smart_card_protocol::{
+    BarnettSmartProtocol,
+    discrete_log_cards::{BnCardProtocol, BnParameters, BnPlayerSecretKey, BnPlayerSecretKeyBuf, BnPublicKey, BnPublicKeyBuf, BnZKProofKeyOwnershipBuf},
+};
+
+/// A player's game key. The secret half never leaves the process unless you explicitly save it with `to_saved`.
+#[derive(Clone)]
+pub struct GameKeys {
+    pub pk: BnPublicKey,
+    sk: BnPlayerSecretKey,
+}
+
+/// `GameKeys` in a form that can be written to disk. Anyone holding this can see your cards, so keep it private
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedKeys {
+    pub pk: BnPublicKeyBuf,
+    pub sk: BnPlayerSecretKeyBuf,
+}
+
+impl GameKeys {
+    pub fn generate<R: Rng>(rng: &mut R, params: &BnParameters) -> Result<Self> {
+        let (pk, sk) = BnCardProtocol::player_keygen(rng, params).map_err(|err| anyhow!("failed to generate game key: {:?}", err))?;
+        Ok(Self { pk, sk })
+    }
+
+    pub(crate) fn sk(&self) -> &BnPlayerSecretKey {
+        &self.sk
+    }
+
+    pub fn pk_buf(&self) -> Result<BnPublicKeyBuf> {
+        BnPublicKeyBuf::serialize(self.pk.clone()).map_err(|err| anyhow!("failed to serialize public key: {:?}", err))
+    }
+
+    /// proves to the contract that `account_id` holds the secret key. The proof is bound to the account, so nobody else can reuse it
+    pub fn prove_ownership<R: Rng>(&self, rng: &mut R, params: &BnParameters, account_id: &str) -> Result<BnZKProofKeyOwnershipBuf> {
+        let proof = BnCardProtocol::prove_key_ownership(rng, params, &self.pk, &self.sk, account_id.as_bytes())
+            .map_err(|err| anyhow!("failed to prove key ownership: {:?}", err))?;
+        BnZKProofKeyOwnershipBuf::serialize(proof).map_err(|err| anyhow!("failed to serialize key ownership proof: {:?}", err))
+    }
+
+    pub fn to_saved(&self) -> Result<SavedKeys> {
+        let sk = BnPlayerSecretKeyBuf::serialize(self.sk.clone()).map_err(|err| anyhow!("failed to serialize secret key: {:?}", err))?;
+        Ok(SavedKeys { pk: self.pk_buf()?, sk })
+    }
+
+    pub fn from_saved(saved: &SavedKeys) -> Result<Self> {
+        let pk = saved.pk.deserialize().map_err(|err| anyhow!("failed to deserialize public key: {:?}", err))?;
+        let sk = saved.sk.deserialize().map_err(|err| anyhow!("failed to deserialize secret key: {:?}", err))?;
+        Ok(Self { pk, sk })
+    }
+}
@@ -0,0 +1,324 @@
+//! A client for playing at a rainbase table.
+//!
+//! `TableClient` does the cryptography a player is responsible for - proving key ownership, shuffling, and computing reveal tokens -
+//! and sends the results to the contract through a `Backend`. The player's secret key stays in memory.
+//!
+//! ```ignore
+//! let mut client = TableClient::new(WorkspacesBackend::new(account, contract_id)).await?;
+//! let game_id = client.create(parse_near!("1 N"), None).await?;
+//! // ... others join ...
+//! client.start().await?;
+//! loop {
+//!     client.perform_duties().await?;
+//!     if let Some(cards) = client.peek_hole_cards().await? { /* ... */ }
+//!     if client.is_my_turn_to_bet().await? { client.act(Action::Call).await?; }
+//! }
+//! ```
+
+use anyhow::{anyhow, bail, Result};
+use rand::{rngs::StdRng, SeedableRng};
+use serde_json::{json, Value};
+use barnett_smart_card_protocol::{
+    BarnettSmartProtocol,
+    discrete_log_cards::{
+        BnCardProtocol, BnMaskedCardBuf, BnParameters, BnParamsBuf, BnPublicKeyBuf, BnRevealTokenWithProofBuf, BnScalar, BnShuffleOutputBuf,
+    },
+};
+use proof_essentials::utils::{permutation::Permutation, rand::sample_vector};
+
+mod backend;
+mod cards;
+mod error;
+mod keys;
+mod view;
+
+pub use backend::{Backend, WorkspacesBackend};
+pub use cards::{card_from_index, CardDecoder, DECK_SIZE};
+pub use error::ContractError;
+pub use keys::{GameKeys, SavedKeys};
+pub use view::{Action, Bet, Chips, GameId, GameView, Phase};
+pub use poker::Card;
+
+pub struct TableClient<B: Backend> {
+    backend: B,
+    params: BnParameters,
+    keys: GameKeys,
+    decoder: CardDecoder,
+    rng: StdRng,
+    game_id: Option<GameId>,
+}
+
+impl<B: Backend> TableClient<B> {
+    /// fetches the trusted setup from the contract and generates a fresh game key
+    pub async fn new(backend: B) -> Result<Self> {
+        let params = fetch_params(&backend).await?;
+        let mut rng = StdRng::from_entropy();
+        let keys = GameKeys::generate(&mut rng, &params)?;
+        Self::build(backend, params, keys, rng)
+    }
+
+    /// like `new`, but plays with keys you already have, e.g. ones saved with `GameKeys::to_saved`
+    pub async fn with_keys(backend: B, keys: GameKeys) -> Result<Self> {
+        let params = fetch_params(&backend).await?;
+        Self::build(backend, params, keys, StdRng::from_entropy())
+    }
+
+    fn build(backend: B, params: BnParameters, keys: GameKeys, rng: StdRng) -> Result<Self> {
+        Ok(Self { backend, params, keys, decoder: CardDecoder::new()?, rng, game_id: None })
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    pub fn keys(&self) -> &GameKeys {
+        &self.keys
+    }
+
+    pub fn game_id(&self) -> Option<GameId> {
+        self.game_id
+    }
+
+    /// points the client at a table you're already seated at, e.g. after restarting
+    pub fn set_game(&mut self, game_id: GameId) {
+        self.game_id = Some(game_id);
+    }
+
+    fn current_game(&self) -> Result<GameId> {
+        self.game_id.ok_or_else(|| anyhow!("not at a table - create or join one first"))
+    }
+
+    fn key_args(&mut self) -> Result<(BnPublicKeyBuf, Value)> {
+        let proof = self.keys.prove_ownership(&mut self.rng, &self.params, &self.backend.account_id())?;
+        Ok((self.keys.pk_buf()?, serde_json::to_value(proof)?))
+    }
+
+    /// opens a lobby, buying in with `buy_in` yoctoNEAR. `config` is the contract's `TableConfig` as JSON - `None` for the defaults
+    pub async fn create(&mut self, buy_in: u128, config: Option<Value>) -> Result<GameId> {
+        let (pk, proof) = self.key_args()?;
+        let args = json!({ "creator_pk": pk, "creator_key_ownership_proof": proof, "config": config });
+        let game_id = serde_json::from_value(self.backend.call("create_game", args, buy_in).await?)?;
+        self.game_id = Some(game_id);
+        Ok(game_id)
+    }
+
+    /// joins a lobby, buying in with `buy_in` yoctoNEAR
+    pub async fn join(&mut self, game_id: GameId, buy_in: u128) -> Result<()> {
+        let (pk, proof) = self.key_args()?;
+        self.backend.call("join_game", json!({ "game_id": game_id, "pk": pk, "key_ownership_proof": proof }), buy_in).await?;
+        self.game_id = Some(game_id);
+        Ok(())
+    }
+
+    /// asks for a seat at a game that's already started, buying in with `buy_in` yoctoNEAR
+    pub async fn request_seat(&mut self, game_id: GameId, buy_in: u128) -> Result<()> {
+        let (pk, proof) = self.key_args()?;
+        self.backend.call("request_seat", json!({ "game_id": game_id, "pk": pk, "key_ownership_proof": proof }), buy_in).await?;
+        self.game_id = Some(game_id);
+        Ok(())
+    }
+
+    /// closes the lobby and starts the game. Only the creator can do this
+    pub async fn start(&self) -> Result<()> {
+        self.call("start_game", json!({})).await
+    }
+
+    pub async fn leave(&self) -> Result<()> {
+        self.call("leave_table", json!({})).await
+    }
+
+    pub async fn act(&self, action: Action) -> Result<()> {
+        self.call("act", json!({ "action": action })).await
+    }
+
+    /// calls a method on the current table that takes nothing but the game id (plus `args`), and returns nothing
+    async fn call(&self, method: &str, mut args: Value) -> Result<()> {
+        args["game_id"] = json!(self.current_game()?);
+        self.backend.call(method, args, 0).await?;
+        Ok(())
+    }
+
+    async fn view<T: serde::de::DeserializeOwned>(&self, method: &str, mut args: Value) -> Result<T> {
+        args["game_id"] = json!(self.current_game()?);
+        Ok(serde_json::from_value(self.backend.view(method, args).await?)?)
+    }
+
+    /// the state of the current table. Fails if the game hasn't started yet, or is over
+    pub async fn game(&self) -> Result<GameView> {
+        self.try_game().await?.ok_or_else(|| anyhow!("the game hasn't started, or is over"))
+    }
+
+    /// like `game`, but `None` rather than an error if the game hasn't started yet, or is over
+    pub async fn try_game(&self) -> Result<Option<GameView>> {
+        self.view("get_game", json!({})).await
+    }
+
+    /// the players waiting in the current table's lobby, or `None` once the game has started
+    pub async fn lobby_players(&self) -> Result<Option<Vec<String>>> {
+        let lobby: Option<Value> = self.view("get_lobby", json!({})).await?;
+        lobby.map(|lobby| Ok(serde_json::from_value(lobby["player_account_ids"].clone())?)).transpose()
+    }
+
+    /// what a chip costs, in yoctoNEAR. Buy-ins are paid in yoctoNEAR, everything else is in chips
+    pub async fn yocto_per_chip(&self) -> Result<u128> {
+        let rate: Chips = serde_json::from_value(self.backend.view("get_yocto_per_chip", json!({})).await?)?;
+        Ok(rate.0)
+    }
+
+    /// this player's seat at the table, if they have one
+    pub fn seat(&self, game: &GameView) -> Option<usize> {
+        game.seat_of(&self.backend.account_id())
+    }
+
+    pub async fn is_my_turn_to_bet(&self) -> Result<bool> {
+        let game = self.game().await?;
+        Ok(game.phase.is_betting() && self.seat(&game) == Some(game.turn))
+    }
+
+    async fn deck(&self) -> Result<Vec<BnMaskedCardBuf>> {
+        let deck: Option<Vec<BnMaskedCardBuf>> = self.view("get_deck", json!({})).await?;
+        deck.ok_or_else(|| anyhow!("the deck hasn't been initialized"))
+    }
+
+    /// shuffles and remasks the deck if it's this player's turn to, initializing it first if nobody has yet. Returns whether there was anything to do
+    pub async fn shuffle_my_turn(&mut self) -> Result<bool> {
+        let game = self.game().await?;
+        if game.phase != Phase::Shuffle || self.seat(&game) != Some(game.turn) {
+            return Ok(false);
+        }
+
+        if game.deck_len == 0 {
+            self.call("derive_initial_deck", json!({})).await?;
+        }
+        while !self.game().await?.deck_verified {
+            self.call("verify_initial_deck", json!({})).await?;
+        }
+
+        let agg_pk_buf: BnPublicKeyBuf = self.view("get_aggregate_pubkey", json!({})).await?;
+        let agg_pk = agg_pk_buf.deserialize().map_err(|err| anyhow!("failed to deserialize aggregate key: {:?}", err))?;
+        let deck = self.deck().await?
+            .iter()
+            .map(|card| card.deserialize().map_err(|err| anyhow!("failed to deserialize card: {:?}", err)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let permutation = Permutation::new(&mut self.rng, DECK_SIZE);
+        let masking_factors: Vec<BnScalar> = sample_vector(&mut self.rng, DECK_SIZE);
+        let output = BnCardProtocol::shuffle_and_remask(&mut self.rng, &self.params, &agg_pk, &deck, &permutation, &masking_factors)
+            .map_err(|err| anyhow!("failed to shuffle: {:?}", err))?;
+        let shuffle = BnShuffleOutputBuf::serialize(output).map_err(|err| anyhow!("failed to serialize shuffle: {:?}", err))?;
+
+        self.call("shuffle_deck", json!({ "shuffle": shuffle })).await?;
+        Ok(true)
+    }
+
+    /// this player's reveal tokens for the cards at `card_indices` of the current deck
+    async fn reveal_tokens(&mut self, card_indices: &[usize]) -> Result<Vec<BnRevealTokenWithProofBuf>> {
+        let deck = self.deck().await?;
+        card_indices.iter().map(|&i| {
+            let card = deck[i].deserialize().map_err(|err| anyhow!("failed to deserialize card: {:?}", err))?;
+            let token = BnCardProtocol::compute_reveal_token(&mut self.rng, &self.params, self.keys.sk(), &self.keys.pk, &card)
+                .map_err(|err| anyhow!("failed to compute reveal token: {:?}", err))?;
+            BnRevealTokenWithProofBuf::serialize(token).map_err(|err| anyhow!("failed to serialize reveal token: {:?}", err))
+        }).collect()
+    }
+
+    /// reveals everyone else's hole cards to them once the deck is shuffled. Returns whether there was anything to do
+    pub async fn deal_my_tokens(&mut self) -> Result<bool> {
+        let game = self.game().await?;
+        let seat = match self.seat(&game) {
+            Some(seat) if game.phase == Phase::Deal && !game.revealed_players[seat] => seat,
+            _ => return Ok(false),
+        };
+
+        let card_indices = game.deal_indices(seat);
+        let tokens = self.reveal_tokens(&card_indices).await?;
+        self.call("deal", json!({ "card_indices": card_indices, "reveal_tokens_with_proofs": tokens })).await?;
+        Ok(true)
+    }
+
+    /// reveals the community cards for the current street, or this player's hole cards at showdown. Returns whether there was anything to do
+    pub async fn reveal_street(&mut self) -> Result<bool> {
+        let game = self.game().await?;
+        let seat = match self.seat(&game) {
+            Some(seat) if !game.revealed_players[seat] => seat,
+            _ => return Ok(false),
+        };
+        let card_indices = match game.reveal_indices(seat) {
+            Some(card_indices) => card_indices,
+            None => return Ok(false),
+        };
+
+        let tokens = self.reveal_tokens(&card_indices).await?;
+        self.call("reveal", json!({ "card_indices": card_indices, "reveal_tokens_with_proofs": tokens })).await?;
+        Ok(true)
+    }
+
+    /// does whichever of shuffling, dealing and revealing the table is waiting on this player for. Returns whether there was anything to do
+    pub async fn perform_duties(&mut self) -> Result<bool> {
+        Ok(self.shuffle_my_turn().await? || self.deal_my_tokens().await? || self.reveal_street().await?)
+    }
+
+    /// unmasks this player's hole cards, once everyone else has revealed them. `None` before the cards are dealt, or if they weren't dealt in
+    pub async fn peek_hole_cards(&mut self) -> Result<Option<[Card; 2]>> {
+        let game = self.game().await?;
+        let seat = match self.seat(&game) {
+            Some(seat) if game.dealt_in[seat] && !matches!(game.phase, Phase::Shuffle | Phase::Deal | Phase::Finished) => seat,
+            _ => return Ok(None),
+        };
+
+        let cards = self.unmask(&game, &[2 * seat, 2 * seat + 1], Some(seat)).await?;
+        let cards: [Card; 2] = cards.try_into().map_err(|_| anyhow!("expected two hole cards"))?;
+        Ok(Some(cards))
+    }
+
+    /// the community cards revealed so far - none preflop, then the flop, turn and river as each street's reveals finish
+    pub async fn community_cards(&mut self) -> Result<Vec<Card>> {
+        let game = self.game().await?;
+        let community = 2 * game.num_players();
+        let card_indices: Vec<usize> = (community..community + game.num_community_cards()).collect();
+        self.unmask(&game, &card_indices, None).await
+    }
+
+    /// unmasks the cards at `card_indices` with the reveal tokens on chain. `own_seat`'s tokens are computed locally instead, for cards only
+    /// this player is meant to see
+    async fn unmask(&mut self, game: &GameView, card_indices: &[usize], own_seat: Option<usize>) -> Result<Vec<Card>> {
+        if card_indices.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let pks = game.player_game_pubkeys.iter()
+            .map(|pk| pk.deserialize().map_err(|err| anyhow!("failed to deserialize public key: {:?}", err)))
+            .collect::<Result<Vec<_>>>()?;
+        let deck = self.deck().await?;
+
+        let mut cards = Vec::new();
+        for &card_idx in card_indices {
+            let masked = deck[card_idx].deserialize().map_err(|err| anyhow!("failed to deserialize card: {:?}", err))?;
+            let tokens: Option<Vec<Option<BnRevealTokenWithProofBuf>>> = self.view("get_reveal_tokens", json!({ "card_index": card_idx })).await?;
+            let tokens = tokens.ok_or_else(|| anyhow!("no reveal tokens for card {}", card_idx))?;
+
+            let mut decryption_key = Vec::new();
+            for (player, token) in tokens.into_iter().enumerate().filter(|&(player, _)| game.dealt_in[player]) {
+                let (token, proof) = if Some(player) == own_seat {
+                    BnCardProtocol::compute_reveal_token(&mut self.rng, &self.params, self.keys.sk(), &self.keys.pk, &masked)
+                        .map_err(|err| anyhow!("failed to compute reveal token: {:?}", err))?
+                } else {
+                    match token {
+                        Some(token) => token.deserialize().map_err(|err| anyhow!("failed to deserialize reveal token: {:?}", err))?,
+                        None => bail!("{} hasn't revealed card {} yet", game.player_account_ids[player], card_idx),
+                    }
+                };
+                decryption_key.push((token, proof, pks[player].clone()));
+            }
+
+            let card = BnCardProtocol::unmask(&self.params, &decryption_key, &masked, false).map_err(|err| anyhow!("failed to unmask card: {:?}", err))?;
+            cards.push(self.decoder.decode(card)?);
+        }
+        Ok(cards)
+    }
+}
+
+async fn fetch_params<B: Backend>(backend: &B) -> Result<BnParameters> {
+    let params: BnParamsBuf = serde_json::from_value(backend.view("get_params", json!({})).await?)?;
+    params.deserialize().map_err(|err| anyhow!("failed to deserialize trusted setup params: {:?}", err))
+}
@@ -0,0 +1,170 @@
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use barnett_smart_card_protocol::discrete_log_cards::BnPublicKeyBuf;
+
+pub type GameId = [u8; 4];
+
+/// An amount of chips. The contract sends and expects these as strings, since JSON numbers can't hold a `u128`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Chips(pub u128);
+
+impl Serialize for Chips {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Chips {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map(Chips).map_err(serde::de::Error::custom)
+    }
+}
+
+impl fmt::Display for Chips {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Phase {
+    Shuffle,
+    Deal,
+    Bet0,
+    Flop,
+    Bet1,
+    Turn,
+    Bet2,
+    River,
+    Bet3,
+    ShowdownReveal,
+    Showdown,
+    Finished,
+}
+
+impl Phase {
+    pub fn is_betting(&self) -> bool {
+        matches!(self, Phase::Bet0 | Phase::Bet1 | Phase::Bet2 | Phase::Bet3)
+    }
+}
+
+/// What a player can do when it's their turn to bet. Amounts are what the player will have in for the betting round, not an increment
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Check,
+    Call,
+    Bet(Chips),
+    Raise { to: Chips },
+    AllIn,
+    Fold,
+}
+
+/// What a player has in the pot this hand
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Bet {
+    AllIn(Chips),
+    In(Chips),
+    Folded(Chips),
+}
+
+impl Bet {
+    pub fn amount(&self) -> Chips {
+        match *self {
+            Bet::AllIn(amount) | Bet::In(amount) | Bet::Folded(amount) => amount,
+        }
+    }
+
+    pub fn is_folded(&self) -> bool {
+        matches!(self, Bet::Folded(_))
+    }
+}
+
+/// The parts of the contract's `get_game` view a client needs to play. The view has more fields than this - they're ignored
+#[derive(Deserialize, Clone, Debug)]
+pub struct GameView {
+    pub id: GameId,
+    pub player_account_ids: Vec<String>,
+    pub phase: Phase,
+    pub turn: usize,
+    pub dealer: usize,
+
+    /// what each player has put in this hand, including earlier betting rounds
+    pub bets: Vec<Bet>,
+
+    /// what each player has put in this betting round
+    pub street_bets: Vec<Chips>,
+    pub current_bet: Chips,
+    pub min_raise: Chips,
+
+    /// chips each player has behind, not counting what they've bet
+    pub balances: Vec<Chips>,
+    pub dealt_in: Vec<bool>,
+    pub revealed_players: Vec<bool>,
+    pub player_game_pubkeys: Vec<BnPublicKeyBuf>,
+    pub deck_len: usize,
+    pub deck_verified: bool,
+}
+
+impl GameView {
+    pub fn num_players(&self) -> usize {
+        self.player_account_ids.len()
+    }
+
+    pub fn seat_of(&self, account_id: &str) -> Option<usize> {
+        self.player_account_ids.iter().position(|id| id == account_id)
+    }
+
+    pub fn pot(&self) -> Chips {
+        Chips(self.bets.iter().map(|bet| bet.amount().0).sum())
+    }
+
+    /// how many community cards have been revealed, once the street's reveal phase is over
+    pub fn num_community_cards(&self) -> usize {
+        match self.phase {
+            Phase::Bet1 | Phase::Turn => 3,
+            Phase::Bet2 | Phase::River => 4,
+            Phase::Bet3 | Phase::ShowdownReveal | Phase::Showdown => 5,
+            _ => 0,
+        }
+    }
+
+    /// the hole cards `seat` reveals for everyone else while dealing - every other dealt in player's. Seat `i` is dealt cards `2i` and `2i + 1`
+    pub fn deal_indices(&self, seat: usize) -> Vec<usize> {
+        (0..2 * self.num_players()).filter(|&i| i / 2 != seat && self.dealt_in[i / 2]).collect()
+    }
+
+    /// the cards `seat` has to reveal in the current phase, if it's a reveal phase - the community cards for the street, or their own hole cards at showdown
+    pub fn reveal_indices(&self, seat: usize) -> Option<Vec<usize>> {
+        let community = 2 * self.num_players();
+        let indices = match self.phase {
+            Phase::Flop => (community..community + 3).collect(),
+            Phase::Turn => vec![community + 3],
+            Phase::River => vec![community + 4],
+            Phase::ShowdownReveal => vec![2 * seat, 2 * seat + 1],
+            _ => return None,
+        };
+        Some(indices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn json_format() {
+        assert_eq!(serde_json::to_value(Action::Check).unwrap(), json!("check"));
+        assert_eq!(serde_json::to_value(Action::Bet(Chips(20))).unwrap(), json!({ "bet": "20" }));
+        assert_eq!(serde_json::to_value(Action::Raise { to: Chips(40) }).unwrap(), json!({ "raise": { "to": "40" } }));
+        assert_eq!(serde_json::from_value::<Phase>(json!("SHOWDOWN_REVEAL")).unwrap(), Phase::ShowdownReveal);
+        assert_eq!(serde_json::from_value::<Phase>(json!("BET2")).unwrap(), Phase::Bet2);
+        assert_eq!(serde_json::from_value::<Chips>(json!("1000")).unwrap(), Chips(1000));
+        assert_eq!(serde_json::from_value::<Bet>(json!({ "all_in": "50" })).unwrap(), Bet::AllIn(Chips(50)));
+    }
+}
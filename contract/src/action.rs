@@ -0,0 +1,82 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+
+use rainbase_core::{Action, Table};
+
+/// What a player can do when it's their turn to bet. Amounts are in chips, and are what the player will have in for the current betting round, not an increment.
+///
+/// In JSON these look like `"check"`, `{"bet": "20"}` or `{"raise": {"to": "40"}}`.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum PlayerAction {
+    Check,
+    Call,
+    /// open the betting when there's nothing to call
+    Bet(U128),
+    Raise { to: U128 },
+    AllIn,
+    Fold,
+}
+
+impl PlayerAction {
+    /// the action described by `bet`'s flags, if exactly one of them is set
+    pub fn from_flags(call: bool, check: bool, all_in: bool, fold: bool, raise: Option<u128>) -> Option<Self> {
+        match (call, check, all_in, fold, raise) {
+            (true, false, false, false, None) => Some(PlayerAction::Call),
+            (false, true, false, false, None) => Some(PlayerAction::Check),
+            (false, false, true, false, None) => Some(PlayerAction::AllIn),
+            (false, false, false, true, None) => Some(PlayerAction::Fold),
+            (false, false, false, false, Some(to)) => Some(PlayerAction::Raise { to: U128(to) }),
+            _ => None,
+        }
+    }
+
+    /// a raise with nothing to call opens the betting instead. `bet`'s flags have no separate way to open it
+    pub fn opening_if_unopened(self, table: &Table) -> Self {
+        match self {
+            PlayerAction::Raise { to } if table.current_bet == 0 => PlayerAction::Bet(to),
+            action => action,
+        }
+    }
+}
+
+impl From<PlayerAction> for Action {
+    fn from(action: PlayerAction) -> Self {
+        match action {
+            PlayerAction::Check => Action::Check,
+            PlayerAction::Call => Action::Call,
+            PlayerAction::Bet(U128(amount)) => Action::Bet(amount),
+            PlayerAction::Raise { to: U128(to) } => Action::Raise { to },
+            PlayerAction::AllIn => Action::AllIn,
+            PlayerAction::Fold => Action::Fold,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::serde_json::{self, json};
+
+    #[test]
+    fn json_format() {
+        assert_eq!(serde_json::to_value(PlayerAction::Check).unwrap(), json!("check"));
+        assert_eq!(serde_json::to_value(PlayerAction::AllIn).unwrap(), json!("all_in"));
+        assert_eq!(serde_json::to_value(PlayerAction::Bet(U128(20))).unwrap(), json!({ "bet": "20" }));
+        assert_eq!(serde_json::to_value(PlayerAction::Raise { to: U128(40) }).unwrap(), json!({ "raise": { "to": "40" } }));
+
+        let action: PlayerAction = serde_json::from_value(json!({ "raise": { "to": "40" } })).unwrap();
+        assert_eq!(action, PlayerAction::Raise { to: U128(40) });
+    }
+
+    #[test]
+    fn bet_flags() {
+        assert_eq!(PlayerAction::from_flags(true, false, false, false, None), Some(PlayerAction::Call));
+        assert_eq!(PlayerAction::from_flags(false, false, false, false, Some(30)), Some(PlayerAction::Raise { to: U128(30) }));
+        assert_eq!(PlayerAction::from_flags(false, false, false, false, None), None);
+        assert_eq!(PlayerAction::from_flags(true, true, false, false, None), None);
+        assert_eq!(PlayerAction::from_flags(false, false, false, true, Some(30)), None);
+    }
+}
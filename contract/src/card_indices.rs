@@ -0,0 +1,169 @@
+use std::fmt;
+
+use near_sdk::serde::{Deserialize, Serialize};
+
+use crate::Phase;
+
+/// Why the card indices submitted alongside a batch of reveal tokens were rejected.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum CardIndexError {
+    /// there has to be exactly one card index per reveal token
+    TokenCountMismatch { indices: usize, tokens: usize },
+
+    /// the same card was submitted more than once
+    Duplicate(usize),
+
+    /// card indices have to be in ascending order
+    NotSorted,
+
+    /// the player didn't reveal the number of cards the phase calls for
+    WrongCount { expected: usize, got: usize },
+
+    /// the player tried to reveal a card they aren't supposed to reveal right now
+    Unexpected(usize),
+
+    /// cards can't be revealed in the current phase
+    WrongPhase,
+}
+
+impl fmt::Display for CardIndexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CardIndexError::TokenCountMismatch { indices, tokens } => write!(f, "got {} card indices but {} reveal tokens", indices, tokens),
+            CardIndexError::Duplicate(idx) => write!(f, "card {} was revealed more than once", idx),
+            CardIndexError::NotSorted => write!(f, "card indices must be sorted"),
+            CardIndexError::WrongCount { expected, got } => write!(f, "expected {} cards to be revealed, got {}", expected, got),
+            CardIndexError::Unexpected(idx) => write!(f, "card {} should not be revealed", idx),
+            CardIndexError::WrongPhase => write!(f, "cannot reveal cards in this phase"),
+        }
+    }
+}
+
+/// the hole cards `player` has to provide reveal tokens for while dealing.
+/// the player in seat `i` is dealt cards `2i` and `2i + 1`, so they reveal the hole cards of everyone else dealt in
+/// (`dealt_in[seat]`) but not their own. Seats that are sitting out keep their cards in the deck, they just never get revealed
+pub fn deal_indices(dealt_in: &[bool], player: usize) -> Vec<usize> {
+    (0..2 * dealt_in.len()).filter(|&i| i / 2 != player && dealt_in[i / 2]).collect()
+}
+
+/// the cards `player` has to reveal in `phase` - the community cards for the street, or their own hole cards at showdown
+pub fn reveal_indices(phase: &Phase, num_players: usize, player: usize) -> Result<Vec<usize>, CardIndexError> {
+    let community = 2 * num_players;
+    let indices = match phase {
+        Phase::FLOP => (community..community + 3).collect(),
+        Phase::TURN => vec![community + 3],
+        Phase::RIVER => vec![community + 4],
+        Phase::SHOWDOWN_REVEAL => vec![player * 2, player * 2 + 1],
+        _ => return Err(CardIndexError::WrongPhase),
+    };
+    Ok(indices)
+}
+
+/// checks that `card_indices` are exactly the (sorted) `expected` indices, with one reveal token each
+pub fn check_card_indices(card_indices: &[usize], num_tokens: usize, expected: &[usize]) -> Result<(), CardIndexError> {
+    if card_indices.len() != num_tokens {
+        return Err(CardIndexError::TokenCountMismatch { indices: card_indices.len(), tokens: num_tokens });
+    }
+
+    let mut sorted = card_indices.to_vec();
+    sorted.sort_unstable();
+    if let Some(w) = sorted.windows(2).find(|w| w[0] == w[1]) {
+        return Err(CardIndexError::Duplicate(w[0]));
+    }
+    if sorted != card_indices {
+        return Err(CardIndexError::NotSorted);
+    }
+
+    if card_indices.len() != expected.len() {
+        return Err(CardIndexError::WrongCount { expected: expected.len(), got: card_indices.len() });
+    }
+    match card_indices.iter().zip(expected.iter()).find(|(got, expected)| got != expected) {
+        Some((&got, _)) => Err(CardIndexError::Unexpected(got)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deal_skips_own_hole_cards() {
+        assert_eq!(deal_indices(&[true; 4], 0), vec![2, 3, 4, 5, 6, 7]);
+        assert_eq!(deal_indices(&[true; 4], 2), vec![0, 1, 2, 3, 6, 7]);
+        assert_eq!(deal_indices(&[true; 2], 1), vec![0, 1]);
+    }
+
+    #[test]
+    fn deal_skips_players_sitting_out() {
+        assert_eq!(deal_indices(&[true, false, true, true], 0), vec![4, 5, 6, 7]);
+        assert_eq!(deal_indices(&[true, true, false], 1), vec![0, 1]);
+    }
+
+    #[test]
+    fn reveal_indices_by_phase() {
+        assert_eq!(reveal_indices(&Phase::FLOP, 4, 1), Ok(vec![8, 9, 10]));
+        assert_eq!(reveal_indices(&Phase::TURN, 4, 1), Ok(vec![11]));
+        assert_eq!(reveal_indices(&Phase::RIVER, 4, 1), Ok(vec![12]));
+        assert_eq!(reveal_indices(&Phase::SHOWDOWN_REVEAL, 4, 1), Ok(vec![2, 3]));
+    }
+
+    #[test]
+    fn reveal_rejected_outside_reveal_phases() {
+        for phase in [Phase::SHUFFLE, Phase::DEAL, Phase::BET0, Phase::BET1, Phase::BET2, Phase::BET3, Phase::SHOWDOWN] {
+            assert_eq!(reveal_indices(&phase, 4, 0), Err(CardIndexError::WrongPhase));
+        }
+    }
+
+    #[test]
+    fn accepts_expected_indices() {
+        let expected = deal_indices(&[true; 3], 1);
+        assert_eq!(check_card_indices(&[0, 1, 4, 5], 4, &expected), Ok(()));
+    }
+
+    #[test]
+    fn rejects_token_count_mismatch() {
+        let expected = deal_indices(&[true; 3], 1);
+        assert_eq!(
+            check_card_indices(&[0, 1, 4, 5], 3, &expected),
+            Err(CardIndexError::TokenCountMismatch { indices: 4, tokens: 3 }),
+        );
+    }
+
+    #[test]
+    fn rejects_duplicates() {
+        let expected = deal_indices(&[true; 3], 1);
+        assert_eq!(check_card_indices(&[0, 1, 1, 5], 4, &expected), Err(CardIndexError::Duplicate(1)));
+        assert_eq!(check_card_indices(&[5, 0, 1, 5], 4, &expected), Err(CardIndexError::Duplicate(5)));
+    }
+
+    #[test]
+    fn rejects_unsorted() {
+        let expected = deal_indices(&[true; 3], 1);
+        assert_eq!(check_card_indices(&[1, 0, 4, 5], 4, &expected), Err(CardIndexError::NotSorted));
+    }
+
+    #[test]
+    fn rejects_wrong_count() {
+        // skipping a community card
+        let expected = reveal_indices(&Phase::FLOP, 3, 0).unwrap();
+        assert_eq!(check_card_indices(&[6, 7], 2, &expected), Err(CardIndexError::WrongCount { expected: 3, got: 2 }));
+    }
+
+    #[test]
+    fn rejects_unexpected_cards() {
+        // revealing your own hole card while dealing
+        let expected = deal_indices(&[true; 3], 1);
+        assert_eq!(check_card_indices(&[0, 1, 2, 5], 4, &expected), Err(CardIndexError::Unexpected(2)));
+
+        // revealing someone else's hole cards at showdown
+        let expected = reveal_indices(&Phase::SHOWDOWN_REVEAL, 3, 0).unwrap();
+        assert_eq!(check_card_indices(&[2, 3], 2, &expected), Err(CardIndexError::Unexpected(2)));
+
+        // revealing the turn during the flop
+        let expected = reveal_indices(&Phase::FLOP, 3, 0).unwrap();
+        assert_eq!(check_card_indices(&[6, 7, 9], 3, &expected), Err(CardIndexError::Unexpected(9)));
+    }
+}
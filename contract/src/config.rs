@@ -0,0 +1,115 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use rainbase_core::Stakes;
+
+use crate::error::{ensure, ContractError};
+
+pub use rainbase_core::BettingStructure;
+
+const DEFAULT_SMALL_BLIND: u128 = 5;
+const DEFAULT_BIG_BLIND: u128 = 10;
+
+/// buy-ins default to between 20 and 200 big blinds
+const DEFAULT_MIN_BUY_IN: u128 = 20 * DEFAULT_BIG_BLIND;
+const DEFAULT_MAX_BUY_IN: u128 = 200 * DEFAULT_BIG_BLIND;
+
+/// The stakes a table plays for, chosen by the creator in `create_game` and fixed for the life of the game.
+/// All amounts are in chips. Any field left out of the JSON gets its default, so `{"small_blind": "25", "big_blind": "50"}` is a valid config.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(default)]
+pub struct TableConfig {
+    pub small_blind: U128,
+    pub big_blind: U128,
+
+    /// paid by every player at the start of each hand, on top of the blinds. It goes in the pot but doesn't count towards calling
+    pub ante: Option<U128>,
+
+    /// how many chips players can sit down with
+    pub min_buy_in: U128,
+    pub max_buy_in: U128,
+
+    pub betting: BettingStructure,
+}
+
+impl Default for TableConfig {
+    fn default() -> Self {
+        Self {
+            small_blind: U128(DEFAULT_SMALL_BLIND),
+            big_blind: U128(DEFAULT_BIG_BLIND),
+            ante: None,
+            min_buy_in: U128(DEFAULT_MIN_BUY_IN),
+            max_buy_in: U128(DEFAULT_MAX_BUY_IN),
+            betting: BettingStructure::NoLimit,
+        }
+    }
+}
+
+impl TableConfig {
+    pub fn validate(&self) -> Result<(), ContractError> {
+        let invalid = |reason: &str| ContractError::InvalidTableConfig { reason: reason.to_string() };
+
+        ensure!(self.small_blind.0 > 0, invalid("the small blind must be at least one chip"));
+        ensure!(self.small_blind.0 <= self.big_blind.0, invalid("the small blind can't be bigger than the big blind"));
+        ensure!(self.ante != Some(U128(0)), invalid("the ante must be at least one chip if there is one"));
+        ensure!(self.min_buy_in.0 >= self.big_blind.0, invalid("the minimum buy-in must cover the big blind"));
+        ensure!(self.min_buy_in.0 <= self.max_buy_in.0, invalid("the minimum buy-in can't be more than the maximum"));
+        Ok(())
+    }
+
+    /// what the game's `Table` plays for
+    pub fn stakes(&self) -> Stakes {
+        Stakes {
+            small_blind: self.small_blind.0,
+            big_blind: self.big_blind.0,
+            ante: self.ante.map(|ante| ante.0),
+            betting: self.betting,
+        }
+    }
+
+    pub fn check_buy_in(&self, chips: u128) -> Result<(), ContractError> {
+        ensure!(
+            self.min_buy_in.0 <= chips && chips <= self.max_buy_in.0,
+            ContractError::BuyInOutOfRange { min: self.min_buy_in, max: self.max_buy_in }
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::serde_json::{self, json};
+
+    #[test]
+    fn missing_fields_are_defaulted() {
+        let config: TableConfig = serde_json::from_value(json!({ "small_blind": "25", "big_blind": "50", "ante": "5" })).unwrap();
+        assert_eq!(config, TableConfig {
+            small_blind: U128(25),
+            big_blind: U128(50),
+            ante: Some(U128(5)),
+            ..TableConfig::default()
+        });
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn validation() {
+        let invalid = [
+            TableConfig { small_blind: U128(0), ..TableConfig::default() },
+            TableConfig { small_blind: U128(20), ..TableConfig::default() },
+            TableConfig { ante: Some(U128(0)), ..TableConfig::default() },
+            TableConfig { min_buy_in: U128(5), ..TableConfig::default() },
+            TableConfig { min_buy_in: U128(5000), ..TableConfig::default() },
+        ];
+        for config in invalid {
+            assert_eq!(config.validate().map_err(|err| err.code()), Err("INVALID_TABLE_CONFIG".to_string()), "{:?}", config);
+        }
+
+        let config = TableConfig::default();
+        assert_eq!(config.check_buy_in(1000), Ok(()));
+        assert_eq!(config.check_buy_in(100), Err(ContractError::BuyInOutOfRange { min: U128(200), max: U128(2000) }));
+        assert_eq!(config.check_buy_in(2001), Err(ContractError::BuyInOutOfRange { min: U128(200), max: U128(2000) }));
+    }
+}
@@ -0,0 +1,234 @@
+use std::fmt;
+
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::serde_json::{self, Value};
+use near_sdk::{env, FunctionError};
+use rainbase_core::Error;
+
+use crate::card_indices::CardIndexError;
+use crate::events::BetAction;
+use crate::Phase;
+
+/// prefix of the panic message for a `ContractError`, followed by the error as JSON
+const ERROR_PREFIX: &str = "ERR_JSON:";
+
+/// returns `err` from the enclosing function (or closure) unless `cond` holds
+macro_rules! ensure {
+    ($cond:expr, $err:expr) => {
+        if !$cond {
+            return Err($err);
+        }
+    };
+}
+pub(crate) use ensure;
+
+/// Everything a caller can get wrong. Each variant has a stable `code` clients can match on instead of the message.
+///
+/// Methods return these through `#[handle_result]`, so the receipt panics (and any state changes are rolled back) with
+/// `ERR_JSON:{"code": ..., "data": ..., "message": ...}`. Use `ContractError::from_panic_message` to get the error back out.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "code", content = "data", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ContractError {
+    // tables and seats
+    GameNotFound,
+    GameNotInProgress,
+    /// the lobby has already been turned into a game
+    GameAlreadyStarted,
+    NotAPlayer,
+    AlreadyJoined,
+    TableFull,
+    NotCreator,
+    /// topping up, rotating keys and removing a player outright can only happen between hands
+    NotBetweenHands,
+    InvalidTableConfig { reason: String },
+    /// a hand needs at least two players who aren't sitting out
+    NotEnoughPlayers,
+
+    // turn order
+    WrongPhase(Phase),
+    NotYourTurn,
+    /// the player already dealt or revealed this phase
+    AlreadyRevealed,
+
+    // timeouts
+    DeadlineNotPassed,
+    NotWaitingOnAnyone,
+    /// players can't claim a timeout on the phase they're holding up themselves
+    WaitingOnYou,
+
+    // the deck
+    DeckAlreadyInitialized,
+    DeckNotInitialized,
+    DeckAlreadyVerified,
+    DeckNotVerified,
+    WrongDeckSize { expected: usize, got: usize },
+    MissingMaskingProofs,
+
+    // betting
+    IllegalAction(BetAction),
+    InvalidBetFlags,
+    RaiseTooSmall { minimum: U128 },
+    /// pot-limit and fixed-limit games cap how much you can raise to
+    RaiseTooBig { maximum: U128 },
+    InsufficientBalance { balance: U128 },
+
+    // money
+    DepositTooSmall,
+    /// the attached deposit buys fewer chips than the table's minimum buy-in, or more than its maximum
+    BuyInOutOfRange { min: U128, max: U128 },
+    NothingToWithdraw,
+
+    // arguments and cryptography
+    /// an argument couldn't be deserialized - `what` says which
+    Malformed { what: String },
+    InvalidProof(ProofKind),
+    CardIndices(CardIndexError),
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum ProofKind {
+    KeyOwnership,
+    Shuffle,
+    Reveal,
+}
+
+impl ContractError {
+    pub fn malformed(what: &str) -> Self {
+        ContractError::Malformed { what: what.to_string() }
+    }
+
+    /// the stable code for this error, e.g. `NOT_YOUR_TURN`
+    pub fn code(&self) -> String {
+        match serde_json::to_value(self) {
+            Ok(Value::Object(map)) => map["code"].as_str().unwrap_or_default().to_string(),
+            _ => unreachable!("errors always serialize to an object"),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut value = serde_json::to_value(self).expect("failed to serialize error");
+        value["message"] = Value::String(self.to_string());
+        value.to_string()
+    }
+
+    /// finds and decodes a `ContractError` in a panic message, e.g. the failure of a transaction that called the contract
+    pub fn from_panic_message(message: &str) -> Option<Self> {
+        let (_, json) = message.split_once(ERROR_PREFIX)?;
+        let mut value = serde_json::Deserializer::from_str(json).into_iter::<Value>().next()?.ok()?;
+        value.as_object_mut()?.remove("message");
+        serde_json::from_value(value).ok()
+    }
+}
+
+impl FunctionError for ContractError {
+    fn panic(&self) -> ! {
+        env::panic_str(&format!("{}{}", ERROR_PREFIX, self.to_json()))
+    }
+}
+
+impl From<CardIndexError> for ContractError {
+    fn from(err: CardIndexError) -> Self {
+        ContractError::CardIndices(err)
+    }
+}
+
+impl From<Error> for ContractError {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::NotAPlayer => ContractError::NotAPlayer,
+            Error::WrongPhase(phase) => ContractError::WrongPhase(phase),
+            Error::NotYourTurn => ContractError::NotYourTurn,
+            Error::AlreadyRevealed => ContractError::AlreadyRevealed,
+            Error::IllegalAction(action) => ContractError::IllegalAction(action),
+            Error::RaiseTooSmall { minimum } => ContractError::RaiseTooSmall { minimum: U128(minimum) },
+            Error::RaiseTooBig { maximum } => ContractError::RaiseTooBig { maximum: U128(maximum) },
+            Error::InsufficientBalance { balance } => ContractError::InsufficientBalance { balance: U128(balance) },
+            // only the players who aren't stalling can claim a timeout, so this means the claimant is one of the stallers
+            Error::NobodyToPay => ContractError::WaitingOnYou,
+        }
+    }
+}
+
+impl fmt::Display for ContractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContractError::GameNotFound => write!(f, "game does not exist"),
+            ContractError::GameNotInProgress => write!(f, "game is not in progress"),
+            ContractError::GameAlreadyStarted => write!(f, "game is no longer accepting players"),
+            ContractError::NotAPlayer => write!(f, "not a player at this table"),
+            ContractError::AlreadyJoined => write!(f, "already at this table"),
+            ContractError::TableFull => write!(f, "the table is full"),
+            ContractError::NotCreator => write!(f, "only the creator can do that"),
+            ContractError::NotBetweenHands => write!(f, "that can only be done between hands"),
+            ContractError::InvalidTableConfig { reason } => write!(f, "invalid table config: {}", reason),
+            ContractError::NotEnoughPlayers => write!(f, "not enough players are sitting in to deal a hand"),
+            ContractError::WrongPhase(phase) => write!(f, "cannot do that in the {:?} phase", phase),
+            ContractError::NotYourTurn => write!(f, "it is not your turn"),
+            ContractError::AlreadyRevealed => write!(f, "you have already revealed this phase"),
+            ContractError::DeadlineNotPassed => write!(f, "the deadline for this phase has not passed yet"),
+            ContractError::NotWaitingOnAnyone => write!(f, "the game is not waiting on anyone"),
+            ContractError::WaitingOnYou => write!(f, "you cannot claim a timeout while the game is waiting on you"),
+            ContractError::DeckAlreadyInitialized => write!(f, "deck must not have been initialized yet"),
+            ContractError::DeckNotInitialized => write!(f, "deck has not been initialized yet"),
+            ContractError::DeckAlreadyVerified => write!(f, "deck has already been verified"),
+            ContractError::DeckNotVerified => write!(f, "the initial deck has not been verified yet"),
+            ContractError::WrongDeckSize { expected, got } => write!(f, "deck must have {} cards, got {}", expected, got),
+            ContractError::MissingMaskingProofs => write!(f, "there must be a masking proof for every card"),
+            ContractError::IllegalAction(action) => write!(f, "you cannot {:?} right now", action),
+            ContractError::InvalidBetFlags => write!(f, "invalid bet flags"),
+            ContractError::RaiseTooSmall { minimum } => write!(f, "raise amount must be at least {}", minimum.0),
+            ContractError::RaiseTooBig { maximum } => write!(f, "raise amount can be at most {}", maximum.0),
+            ContractError::InsufficientBalance { balance } => write!(f, "not enough chips - you only have {}", balance.0),
+            ContractError::DepositTooSmall => write!(f, "must attach enough NEAR to buy at least one chip"),
+            ContractError::BuyInOutOfRange { min, max } => write!(f, "buy-in must be between {} and {} chips", min.0, max.0),
+            ContractError::NothingToWithdraw => write!(f, "nothing to withdraw"),
+            ContractError::Malformed { what } => write!(f, "failed to deserialize {}", what),
+            ContractError::InvalidProof(kind) => write!(f, "failed to verify {:?} proof", kind),
+            ContractError::CardIndices(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stable_codes() {
+        assert_eq!(ContractError::NotYourTurn.code(), "NOT_YOUR_TURN");
+        assert_eq!(ContractError::WrongPhase(Phase::BET0).code(), "WRONG_PHASE");
+        assert_eq!(ContractError::CardIndices(CardIndexError::NotSorted).code(), "CARD_INDICES");
+    }
+
+    #[test]
+    fn round_trips_through_panic_message() {
+        let errors = [
+            ContractError::NotYourTurn,
+            ContractError::WrongPhase(Phase::SHOWDOWN_REVEAL),
+            ContractError::RaiseTooSmall { minimum: U128(20) },
+            ContractError::IllegalAction(BetAction::Check),
+            ContractError::InvalidProof(ProofKind::Shuffle),
+            ContractError::malformed("shuffle"),
+            ContractError::CardIndices(CardIndexError::WrongCount { expected: 3, got: 2 }),
+        ];
+        for err in errors {
+            // this is roughly what an RPC node hands back for a failed function call
+            let message = format!("Smart contract panicked: {}{}", ERROR_PREFIX, err.to_json());
+            assert_eq!(ContractError::from_panic_message(&message), Some(err));
+        }
+
+        assert_eq!(ContractError::from_panic_message("Smart contract panicked: something else"), None);
+    }
+
+    #[test]
+    fn message_is_included() {
+        assert_eq!(
+            ContractError::RaiseTooSmall { minimum: U128(20) }.to_json(),
+            r#"{"code":"RAISE_TOO_SMALL","data":{"minimum":"20"},"message":"raise amount must be at least 20"}"#,
+        );
+    }
+}
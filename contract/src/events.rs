@@ -0,0 +1,170 @@
+use near_sdk::serde::Serialize;
+use near_sdk::serde_json;
+use near_sdk::{env, json_types::U128, AccountId};
+
+use crate::config::TableConfig;
+use crate::{GameId, Phase};
+
+pub use rainbase_core::BetAction;
+
+const EVENT_STANDARD: &str = "rainbase";
+const EVENT_VERSION: &str = "1.0.0";
+
+/// [NEP-297](https://nomicon.io/Standards/EventsFormat) events, logged for every transition so indexers and the frontend
+/// can rebuild the state of every table from receipts alone.
+#[derive(Serialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum GameEvent {
+    GameCreated {
+        game_id: GameId,
+        creator: AccountId,
+        chips: U128,
+        config: TableConfig,
+    },
+    PlayerJoined {
+        game_id: GameId,
+        account_id: AccountId,
+        chips: U128,
+    },
+    GameStarted {
+        game_id: GameId,
+        players: Vec<AccountId>,
+        dealer: usize,
+    },
+    DeckShuffled {
+        game_id: GameId,
+        account_id: AccountId,
+    },
+    TokensRevealed {
+        game_id: GameId,
+        account_id: AccountId,
+        card_indices: Vec<usize>,
+    },
+    BetPlaced {
+        game_id: GameId,
+        account_id: AccountId,
+        action: BetAction,
+        /// the player's total stake in the pot after the action
+        amount: U128,
+    },
+    PhaseChanged {
+        game_id: GameId,
+        phase: Phase,
+        turn: usize,
+        dealer: usize,
+    },
+    PotAwarded {
+        game_id: GameId,
+        account_id: AccountId,
+        amount: U128,
+    },
+    /// the player swapped in a fresh game key for the next hand
+    KeyRotated {
+        game_id: GameId,
+        account_id: AccountId,
+    },
+    PlayerSatOut {
+        game_id: GameId,
+        account_id: AccountId,
+    },
+    PlayerSatIn {
+        game_id: GameId,
+        account_id: AccountId,
+    },
+    ChipsAdded {
+        game_id: GameId,
+        account_id: AccountId,
+        chips: U128,
+        /// the player's stack after topping up
+        balance: U128,
+    },
+    /// a late joiner bought in. They're seated (with a `player_joined`) once the current hand is over
+    SeatRequested {
+        game_id: GameId,
+        account_id: AccountId,
+        chips: U128,
+    },
+    PlayerLeft {
+        game_id: GameId,
+        account_id: AccountId,
+        /// chips the player left the table with, if any
+        chips: U128,
+        reason: LeaveReason,
+    },
+    /// one player won all the chips and was paid out. The game no longer exists after this
+    GameFinished {
+        game_id: GameId,
+        /// `None` if nobody was left at the table
+        winner: Option<AccountId>,
+        chips: U128,
+    },
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum LeaveReason {
+    /// the player left on their own
+    Left,
+    /// the player held up the shuffle and was removed with their chips
+    TimedOut,
+    /// the player withheld reveal tokens and their chips were split between everyone else
+    Slashed,
+    /// the player ran out of chips
+    Busted,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct EventLog<'a> {
+    standard: &'static str,
+    version: &'static str,
+    #[serde(flatten)]
+    event: &'a GameEvent,
+}
+
+impl GameEvent {
+    pub fn to_json(&self) -> String {
+        let log = EventLog {
+            standard: EVENT_STANDARD,
+            version: EVENT_VERSION,
+            event: self,
+        };
+        serde_json::to_string(&log).expect("failed to serialize event")
+    }
+
+    pub fn emit(&self) {
+        env::log_str(&format!("EVENT_JSON:{}", self.to_json()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nep297_format() {
+        let event = GameEvent::BetPlaced {
+            game_id: [1, 2, 3, 4],
+            account_id: "alice.near".parse().unwrap(),
+            action: BetAction::AllIn,
+            amount: U128(150),
+        };
+        assert_eq!(
+            event.to_json(),
+            r#"{"standard":"rainbase","version":"1.0.0","event":"bet_placed","data":{"game_id":[1,2,3,4],"account_id":"alice.near","action":"all_in","amount":"150"}}"#,
+        );
+
+        let event = GameEvent::PhaseChanged {
+            game_id: [1, 2, 3, 4],
+            phase: Phase::SHOWDOWN_REVEAL,
+            turn: 2,
+            dealer: 1,
+        };
+        assert_eq!(
+            event.to_json(),
+            r#"{"standard":"rainbase","version":"1.0.0","event":"phase_changed","data":{"game_id":[1,2,3,4],"phase":"SHOWDOWN_REVEAL","turn":2,"dealer":1}}"#,
+        );
+    }
+}
@@ -7,17 +7,21 @@
  */
 
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::log;
 use near_sdk::{
     near_bindgen,
     AccountId,
     Balance, 
+    Gas,
+    Promise,
+    PromiseError,
     env,
+    json_types::{U128, U64},
     collections::{
         LookupMap,
     }
 };
-use is_sorted::IsSorted;
 use barnett_smart_card_protocol::{
     BarnettSmartProtocol,
     discrete_log_cards::{
@@ -26,6 +30,7 @@ use barnett_smart_card_protocol::{
         BnMaskedCardBuf,
         BnRevealTokenWithProofBuf,
         BnZKProofKeyOwnershipBuf,
+        BnZKProofMaskingBuf,
         BnShuffleOutputBuf,
         BnCardBuf,
         BnPublicKey,
@@ -40,21 +45,59 @@ use barnett_smart_card_protocol::{
         BnZKProofMasking,
         BnZKProofRemasking,
         BnZKProofReveal,
+        BnScalar,
         get_card_elems_buf,
     }
 };
+use proof_essentials::homomorphic_encryption::{el_gamal::ElGamal, HomomorphicEncryptionScheme};
+use ark_ff::One;
 use rand::{
     Rng,
     SeedableRng,
     rngs::StdRng
 };
 use poker::{Card, Rank, Suit, Evaluator};
+use rainbase_core::{Action, Event, Table};
+
+mod action;
+mod card_indices;
+mod config;
+mod error;
+mod events;
+mod store;
+mod views;
+
+use action::PlayerAction;
+use config::TableConfig;
+use error::{ensure, ContractError, ProofKind};
+use events::{GameEvent, LeaveReason};
+use store::GameStore;
+use views::{GameStateView, LobbyView};
+
+pub use rainbase_core::Phase;
 
 const GAMES_STORAGE_KEY: &'static [u8] = b"GAMES";
 const MAPPING_STORAGE_KEY: &'static [u8] = b"CARD_MAPPING";
+const UNCLAIMED_STORAGE_KEY: &'static [u8] = b"UNCLAIMED";
+
+/// default buy-in rate - 1 chip = 0.001 NEAR
+const DEFAULT_YOCTO_PER_CHIP: Balance = 1_000_000_000_000_000_000_000;
+
+const GAS_FOR_REFUND_CALLBACK: Gas = Gas(5_000_000_000_000);
+
+/// verifying all 52 masking proofs at once costs more gas than a single call gets, so the initial deck is verified this many cards at a time
+const MASKING_PROOFS_PER_CALL: usize = 13;
+
+/// every table plays with a standard 52 card deck
+const DECK_SIZE: usize = 52;
+
+/// everyone at the table gets two cards, and there have to be five left for the board
+const MAX_PLAYERS: usize = (DECK_SIZE - 5) / 2;
+
+const MINUTE: u64 = 60 * 1_000_000_000;
 
-const LITTLE_BLIND_AMOUNT: Balance = 5;
-const BIG_BLIND_AMOUNT: Balance = 10;
+/// the most lobbies `list_open_lobbies` will return in one call
+const MAX_LOBBIES_PER_PAGE: u64 = 50;
 
 type GameId = [u8; 4];
 
@@ -62,9 +105,15 @@ type GameId = [u8; 4];
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct Contract {
-    games: LookupMap<GameId, Game>,
+    games: GameStore,
     trusted_setup_params: BnParamsBuf,
     card_mapping: LookupMap<BnCardBuf, usize>,
+
+    /// how many yoctoNEAR a single chip is worth. Attached deposits are converted to chips at this rate
+    yocto_per_chip: Balance,
+
+    /// yoctoNEAR owed to accounts whose refund transfer failed. They can pull it out with `withdraw_unclaimed`
+    unclaimed: LookupMap<AccountId, Balance>,
 }
 
 fn card_value_to_card(mapping: &LookupMap<BnCardBuf, usize>, value: &BnCardBuf) -> Card {
@@ -79,6 +128,26 @@ fn card_value_to_card(mapping: &LookupMap<BnCardBuf, usize>, value: &BnCardBuf)
     Card::new(RANKS[rank_idx], SUITS[suit_idx])
 }
 
+/// a fresh rng seeded from the block's random seed
+fn seeded_rng() -> StdRng {
+    let seed = env::random_seed();
+    assert!(seed.len() >= 32, "random seed is too short - this should never happen!");
+
+    StdRng::from_seed(seed[0..32].try_into().unwrap())
+}
+
+fn compute_aggregate_pubkey(pp: &BnParamsBuf, player_account_ids: &[AccountId], player_game_pubkeys: &[BnPublicKeyBuf]) -> BnPublicKeyBuf {
+    let pp = pp.deserialize().expect("failed to deserialize public parameters");
+    let mut player_infos = Vec::new();
+    for (account_id, pk) in player_account_ids.iter().zip(player_game_pubkeys.iter()) {
+        let pk = pk.deserialize().expect("failed to deserialize player public key");
+        player_infos.push((pk, account_id.as_bytes()));
+    }
+
+    let aggregate_pubkey = BnCardProtocol::compute_aggregate_key(&pp, &player_infos, None).expect("failed to aggregate public keys");
+    BnPublicKeyBuf::serialize(aggregate_pubkey).expect("failed to serialize aggregate public key")
+}
+
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct GameLobby {
     /// the id for the game. players will use this to join the game
@@ -89,17 +158,29 @@ pub struct GameLobby {
     pub player_account_ids: Vec<AccountId>,
     pub player_game_pubkeys: Vec<BnPublicKeyBuf>,
 
+    /// the chips each player bought in with. The NEAR backing them is held in escrow by the contract
+    pub balances: Vec<Balance>,
+
+    /// how long players get to act in each phase once the game starts
+    pub timeouts: Timeouts,
+
+    /// the stakes, buy-in range and betting structure
+    pub config: TableConfig,
+
     /// this is used to detect stale lobbies. Lobbies more than 30 minutes old will be deleted
     pub created_at: u64,
 }
 
 impl GameLobby {
-    fn new(id: GameId, player_account_ids: Vec<AccountId>, player_game_pubkeys: Vec<BnPublicKeyBuf>) -> Self {
+    fn new(id: GameId, player_account_ids: Vec<AccountId>, player_game_pubkeys: Vec<BnPublicKeyBuf>, balances: Vec<Balance>, timeouts: Timeouts, config: TableConfig) -> Self {
         let created_at = env::block_timestamp();
         Self {
             id,
             player_account_ids,
             player_game_pubkeys,
+            balances,
+            timeouts,
+            config,
             created_at,
         }
     }
@@ -112,10 +193,26 @@ impl GameLobby {
         self.player_account_ids.len()
     }
 
-    fn add_player(&mut self, account_id: AccountId, game_pubkey: BnPublicKeyBuf) {
+    fn add_player(&mut self, account_id: AccountId, game_pubkey: BnPublicKeyBuf, chips: Balance) {
         self.player_account_ids.push(account_id);
         self.player_game_pubkeys.push(game_pubkey);
+        self.balances.push(chips);
     }
+
+    /// removes a player from the lobby, returning the chips they bought in with
+    fn remove_player(&mut self, player_idx: usize) -> Balance {
+        self.player_account_ids.remove(player_idx);
+        self.player_game_pubkeys.remove(player_idx);
+        self.balances.remove(player_idx)
+    }
+}
+
+/// someone who asked for a seat at a game that's already started. They're seated once the current hand is over
+#[derive(BorshDeserialize, BorshSerialize, Clone)]
+pub struct SeatRequest {
+    pub account_id: AccountId,
+    pub game_pubkey: BnPublicKeyBuf,
+    pub chips: Balance,
 }
 
 #[derive(BorshDeserialize, BorshSerialize)]
@@ -125,36 +222,34 @@ pub struct GameState {
 
     // game state
 
-    /// current phase of the game
-    pub phase: Phase,
-
-    /// the current player whose turn it is
-    pub turn: usize,
+    /// whose turn it is, everyone's chips and bets, and what phase the hand is in - everything the rules care about
+    pub table: Table,
 
-    /// the current "dealer". Since everyone shuffles, in practice this is just the player who publishes the initial deck for the round
-    /// this is also used to determine who the little blind, big blind, and action is
-    /// this increases by one each round
-    pub dealer: usize,
+    // seats
 
-    /// used to check which players have revealed.
-    pub revealed_players: Vec<bool>,
+    /// players who asked to sit out. They keep their seat and their chips, but aren't dealt in until they sit back in
+    pub sitting_out: Vec<bool>,
 
-    /// the amounts each player has bet so far
-    pub bets: Vec<BetAmount>,
+    /// players who asked to leave in the middle of a hand. They're cashed out once it's over
+    pub leaving: Vec<bool>,
 
-    /// the current ante
-    pub ante: Balance,
+    /// late joiners waiting for the current hand to finish so they can sit down
+    pub pending_seats: Vec<SeatRequest>,
 
-    /// number of players who have checked
-    pub checks: Vec<bool>,
-
-    /// the number of "chips" each player has
-    pub balances: Vec<Balance>,
+    /// chips owed to players who've left the table, waiting for the contract to send them their NEAR
+    pub cash_outs: Vec<(AccountId, Balance)>,
 
     // TODO: find a more intelligent way to do this
     /// used to detect when the game is "over". Games that haven't been touched for over an hour are considered "over"
+    /// also the start of the clock for the current phase's deadline
     pub last_modified: u64,
 
+    /// how long players get to act in each phase before someone else can call `claim_timeout` on them
+    pub timeouts: Timeouts,
+
+    /// the stakes, buy-in range and betting structure, carried over from the lobby
+    pub config: TableConfig,
+
     // cryptography state
 
     /// public parameters for the protocol
@@ -174,50 +269,59 @@ pub struct GameState {
     /// i.e. reveal_tokens_with_proofs[card_index][player_index] is the reveal token for card_index, revealed by player_index, if they have provided it
     /// a player can only unmask a card once all of the reveal tokens for that card have been received
     pub reveal_tokens_with_proofs: Vec<Vec<Option<BnRevealTokenWithProofBuf>>>,
+
+    /// masking proofs for the initial deck that haven't been checked yet. Cleared once the whole deck is verified
+    pub initial_deck_proofs: Vec<BnZKProofMaskingBuf>,
+
+    /// how many cards of the initial deck have had their masking proofs verified so far.
+    /// nobody can shuffle until every card has been verified
+    pub num_cards_verified: usize,
 }
 
-#[derive(BorshDeserialize, BorshSerialize, Clone, Debug)]
-pub enum BetAmount {
-    /// the player is all-in
-    AllIn,
-    
-    /// the player bet some amount, isn't all-in, and hasn't folded
-    In(Balance),
+/// how long (in nanoseconds, same as block timestamps) players get to act in each phase before anyone else at the table can call `claim_timeout`
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Timeouts {
+    pub shuffle: u64,
+    pub deal: u64,
+    pub bet: u64,
+    pub reveal: u64,
+}
 
-    /// the player folded and is out of the round with their ante still on the table
-    Folded(Balance)
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            shuffle: 5 * MINUTE,
+            deal: 2 * MINUTE,
+            bet: 2 * MINUTE,
+            reveal: 2 * MINUTE,
+        }
+    }
 }
 
 impl GameState {
-    fn new(id: GameId, player_account_ids: Vec<AccountId>, player_game_pubkeys: Vec<BnPublicKeyBuf>, pp: BnParamsBuf) -> Self {
+    fn new(id: GameId, player_account_ids: Vec<AccountId>, player_game_pubkeys: Vec<BnPublicKeyBuf>, balances: Vec<Balance>, timeouts: Timeouts, config: TableConfig, pp: BnParamsBuf) -> Self {
         let num_players = player_account_ids.len();
-        let _pp = pp.deserialize().expect("failed to deserialize public parameters");
-        let mut player_infos = Vec::new();
-        for (account_id, pk) in player_account_ids.iter().zip(player_game_pubkeys.iter()) {
-            let pk = pk.deserialize().expect("failed to deserialize player public key");
-            player_infos.push((pk, account_id.as_bytes()));
-        }
-
-        let aggregate_pubkey = BnCardProtocol::compute_aggregate_key(&_pp, &player_infos, None).expect("failed to aggregate public keys");
-        let aggregate_pubkey = BnPublicKeyBuf::serialize(aggregate_pubkey).expect("failed to serialize aggregate public key");
+        let aggregate_pubkey = compute_aggregate_pubkey(&pp, &player_account_ids, &player_game_pubkeys);
         
         Self {
             id,
             player_account_ids,
-            phase: Phase::SHUFFLE,
-            turn: 0,
-            dealer: 0,
-            ante: 0,
-            checks: vec![false; num_players],
-            revealed_players: vec![false; num_players],
-            bets: vec![BetAmount::In(0); num_players],
-            balances: vec![0; num_players],
+            table: Table::new(balances, config.stakes()),
+            sitting_out: vec![false; num_players],
+            leaving: vec![false; num_players],
+            pending_seats: vec![],
+            cash_outs: vec![],
             last_modified: env::block_timestamp(),
+            timeouts,
+            config,
             pp,
             player_game_pubkeys,
             aggregate_pubkey,
             deck: vec![],
-            reveal_tokens_with_proofs: vec![vec![None; num_players]; 52],
+            reveal_tokens_with_proofs: vec![vec![None; num_players]; DECK_SIZE],
+            initial_deck_proofs: vec![],
+            num_cards_verified: 0,
         }
     }
 
@@ -225,11 +329,6 @@ impl GameState {
         self.player_account_ids.len()
     }
 
-    fn num_cards(&self) -> usize {
-        let pp = self.pp.deserialize().expect("failed to deserialize public parameters");
-        pp.num_cards()
-    }
-
     fn player_index(&self, account_id: &AccountId) -> Option<usize> {
         self.player_account_ids.iter().position(|id| id == account_id)
     }
@@ -242,155 +341,350 @@ impl GameState {
         self.deck = deck;
     }
 
-    fn reset_reveal_tokens(&mut self) {
-        self.reveal_tokens_with_proofs = vec![vec![None; self.num_players()]; self.num_cards()];
+    /// true once every card in the initial deck has been proven to be a masking of the canonical deck
+    fn deck_verified(&self) -> bool {
+        !self.deck.is_empty() && self.num_cards_verified == self.deck.len()
     }
 
-    fn set_reveal_token(&mut self, card_idx: usize, player_idx: usize, token: BnRevealTokenWithProofBuf) {
-        self.reveal_tokens_with_proofs[card_idx][player_idx] = Some(token);
+    fn discard_deck(&mut self) {
+        self.deck = vec![];
+        self.initial_deck_proofs = vec![];
+        self.num_cards_verified = 0;
     }
 
-    fn set_revealed_player(&mut self, player_idx: usize) {
-        self.revealed_players[player_idx] = true;
+    /// masks the canonical deck under the aggregate key with the trivial randomness `r = 1`. Anyone can recompute this,
+    /// so unlike a deck submitted by the creator it doesn't need masking proofs. The shuffles that follow are what hide the cards.
+    /// With `r = 1` each card is just `(g, card + aggregate key)`, so it's encrypted directly - `BnCardProtocol::mask` would
+    /// also build a masking proof for every card, which is far more than a single call's gas
+    fn derive_initial_deck(&mut self, pp: &BnParameters) {
+        let aggregate_pubkey = self.aggregate_pubkey.deserialize().expect("failed to deserialize aggregate pubkey");
+        let card_values = get_card_elems_buf(52).expect("failed to get card values");
+
+        let mut deck = Vec::new();
+        for card in card_values.iter() {
+            let card = card.deserialize().expect("failed to deserialize card value");
+            let masked_card = ElGamal::encrypt(&pp.enc_parameters, &aggregate_pubkey, &card, &BnScalar::one()).expect("failed to mask card");
+            deck.push(BnMaskedCardBuf::serialize(masked_card).expect("failed to serialize masked card"));
+        }
+
+        self.initial_deck_proofs = vec![];
+        self.num_cards_verified = deck.len();
+        self.set_deck(deck);
     }
 
-    fn reset_revealed_players(&mut self) {
-        self.revealed_players = vec![false; self.num_players()];
+    /// checks the masking proofs for the next batch of cards in the initial deck.
+    /// if any of them don't check out, the whole deck is thrown out so the creator can submit a new one. Returns whether the batch was valid
+    fn verify_initial_deck_batch(&mut self, pp: &BnParameters) -> bool {
+        let aggregate_pubkey = self.aggregate_pubkey.deserialize().expect("failed to deserialize aggregate pubkey");
+        let card_values = get_card_elems_buf(self.deck.len()).expect("failed to get card values");
+
+        let end = (self.num_cards_verified + MASKING_PROOFS_PER_CALL).min(self.deck.len());
+        for i in self.num_cards_verified..end {
+            let card = card_values[i].deserialize().expect("failed to deserialize card value");
+            let valid = match (self.deck[i].deserialize(), self.initial_deck_proofs[i].deserialize()) {
+                (Ok(masked_card), Ok(proof)) => BnCardProtocol::verify_mask(pp, &aggregate_pubkey, &card, &masked_card, &proof).is_ok(),
+                _ => false,
+            };
+
+            if !valid {
+                log!("masking proof for card {} is invalid, discarding the initial deck", i);
+                self.discard_deck();
+                return false;
+            }
+        }
+
+        self.num_cards_verified = end;
+        if self.deck_verified() {
+            self.initial_deck_proofs = vec![];
+        }
+        true
+    }
+
+    fn finished(&self) -> bool {
+        matches!(self.table.phase, Phase::FINISHED)
     }
 
-    fn all_players_revealed(&self) -> bool {
-        self.revealed_players.iter().all(|&x| x)
+    /// true if nobody has shuffled yet this hand, so it's safe to change who's sitting at the table
+    fn between_hands(&self) -> bool {
+        self.table.between_hands()
     }
 
-    fn num_checks(&self) -> usize {
-        self.checks.iter().filter(|&x| *x).count()
+    /// true if `account_id` is sitting at the table or waiting for a seat
+    fn has_seat_or_request(&self, account_id: &AccountId) -> bool {
+        self.player_account_ids.contains(account_id) || self.pending_seats.iter().any(|req| &req.account_id == account_id)
     }
 
-    fn reset_checks(&mut self) {
-        self.checks = vec![false; self.num_players()];
+    /// the account ids and game keys of the players dealt into the current hand - the ones the aggregate key is made from
+    fn hand_keys(&self) -> (Vec<AccountId>, Vec<BnPublicKeyBuf>) {
+        (0..self.num_players())
+            .filter(|&i| self.table.dealt_in[i])
+            .map(|i| (self.player_account_ids[i].clone(), self.player_game_pubkeys[i].clone()))
+            .unzip()
     }
 
-    fn set_player_checked(&mut self, player_idx: usize) {
-        self.checks[player_idx] = true;
+    /// recomputes the aggregate key for whoever is dealt into the current hand. Called right before the deck is initialized
+    fn refresh_aggregate_pubkey(&mut self) {
+        let (account_ids, pubkeys) = self.hand_keys();
+        self.aggregate_pubkey = compute_aggregate_pubkey(&self.pp, &account_ids, &pubkeys);
     }
 
-    fn unset_player_checked(&mut self, player_idx: usize) {
-        self.checks[player_idx] = false;
+    /// removes a player from the table between hands, returning their remaining chips
+    fn remove_player(&mut self, player_idx: usize) -> Result<Balance, ContractError> {
+        ensure!(self.between_hands(), ContractError::NotBetweenHands);
+        Ok(self.eject_player(player_idx))
     }
 
-    fn num_players_in(&self) -> usize {
-        (0..self.bets.len()).filter(|&i| !self.player_is_folded(i)).count()
+    /// removes a player from the table, returning their remaining chips, and starts a fresh hand for everyone else
+    fn eject_player(&mut self, player_idx: usize) -> Balance {
+        let hand_keys = self.hand_keys();
+        let chips = self.remove_seat(player_idx);
+        self.table.phase = Phase::SHUFFLE;
+        self.start_hand(hand_keys);
+        chips
     }
 
-    fn num_players_checked(&self) -> usize {
-        self.checks.iter().filter(|&x| *x).count()
+    /// takes a player's seat away, keeping the button where it is. Returns the chips they had behind
+    fn remove_seat(&mut self, player_idx: usize) -> Balance {
+        self.player_account_ids.remove(player_idx);
+        self.player_game_pubkeys.remove(player_idx);
+        self.sitting_out.remove(player_idx);
+        self.leaving.remove(player_idx);
+        self.table.remove_seat(player_idx)
     }
 
-    fn enough_players_checked(&self) -> bool {
-        self.num_players_in() == self.num_players_checked()
+    fn add_seat(&mut self, request: SeatRequest) {
+        GameEvent::PlayerJoined { game_id: self.id, account_id: request.account_id.clone(), chips: U128(request.chips) }.emit();
+        self.player_account_ids.push(request.account_id);
+        self.player_game_pubkeys.push(request.game_pubkey);
+        self.table.add_seat(request.chips);
+        self.sitting_out.push(false);
+        self.leaving.push(false);
     }
 
-    fn set_folded_player(&mut self, player_idx: usize) {
-        self.bets[player_idx] = match self.bets[player_idx] {
-            BetAmount::In(amt) => BetAmount::Folded(amt),
-            BetAmount::AllIn => BetAmount::Folded(self.balances[player_idx]),
-            _ => panic!("player is already folded")
+    /// sets the table up for a new hand - players who asked to leave are cashed out, players who've run out of chips are eliminated,
+    /// late joiners are seated, and everyone who isn't sitting out is dealt in. If that leaves fewer than two players, the game is over.
+    /// `prev_hand_keys` are the keys the current deck is encrypted under. If the players dealt in have changed, that deck is thrown out,
+    /// and the aggregate key is recomputed for the new players when the next one is initialized
+    fn start_hand(&mut self, prev_hand_keys: (Vec<AccountId>, Vec<BnPublicKeyBuf>)) {
+        let leavers = (0..self.num_players()).filter(|&i| self.leaving[i]).collect::<Vec<_>>();
+        for &player in leavers.iter().rev() {
+            let account_id = self.player_account_id(player);
+            let chips = self.remove_seat(player);
+            GameEvent::PlayerLeft { game_id: self.id, account_id: account_id.clone(), chips: U128(chips), reason: LeaveReason::Left }.emit();
+            self.cash_outs.push((account_id, chips));
+        }
+        let busted = (0..self.num_players()).filter(|&i| self.table.balances[i] == 0).collect::<Vec<_>>();
+        for &player in busted.iter().rev() {
+            GameEvent::PlayerLeft { game_id: self.id, account_id: self.player_account_id(player), chips: U128(0), reason: LeaveReason::Busted }.emit();
+            self.remove_seat(player);
         }
+        for request in std::mem::take(&mut self.pending_seats) {
+            self.add_seat(request);
+        }
+        if self.num_players() < 2 {
+            self.finish();
+            return;
+        }
+
+        self.table.dealt_in = self.sitting_out.iter().map(|&x| !x).collect();
+        if self.num_players() > 0 && !self.table.dealt_in[self.table.dealer] {
+            self.table.dealer = self.table.next_dealt_in(self.table.dealer);
+        }
+        let (prev_account_ids, prev_pubkeys) = prev_hand_keys;
+        let (account_ids, pubkeys) = self.hand_keys();
+        if account_ids != prev_account_ids || !pubkeys.iter().map(|pk| &pk.buf).eq(prev_pubkeys.iter().map(|pk| &pk.buf)) {
+            self.discard_deck();
+        }
+        self.new_round();
     }
 
-    fn reset_bets(&mut self) {
-        self.bets = vec![BetAmount::In(0); self.num_players()];
+    /// whoever's left at the table has all the chips, so they're cashed out and the game is over
+    fn finish(&mut self) {
+        self.table.phase = Phase::FINISHED;
+        let winner = self.player_account_ids.first().cloned();
+        let chips = self.table.balances.iter().sum();
+        self.table.balances.iter_mut().for_each(|balance| *balance = 0);
+        if let Some(account_id) = &winner {
+            self.cash_outs.push((account_id.clone(), chips));
+        }
+        GameEvent::GameFinished { game_id: self.id, winner, chips: U128(chips) }.emit();
     }
 
-    fn player_can_check(&self) -> bool {
-        let player_idx = self.turn;
-        match self.bets[player_idx] {
-            BetAmount::In(amt) => amt == self.ante,
-            BetAmount::AllIn => true,
-            BetAmount::Folded(_) => false
+    /// `player` will sit out from the next hand on. Between hands, that's the hand about to start
+    fn sit_out(&mut self, player: usize) {
+        self.sitting_out[player] = true;
+        if self.between_hands() {
+            self.start_hand(self.hand_keys());
         }
     }
 
-    fn player_can_call(&self) -> bool {
-        let player_idx = self.turn;
-        match self.bets[player_idx] {
-            BetAmount::In(_) => self.balances[player_idx] >= self.ante,
-            BetAmount::AllIn => false,
-            BetAmount::Folded(_) => false
+    /// `player` will be dealt in again from the next hand on, and won't be cashed out if they'd asked to leave
+    fn sit_in(&mut self, player: usize) {
+        self.sitting_out[player] = false;
+        self.leaving[player] = false;
+        if self.between_hands() {
+            self.start_hand(self.hand_keys());
         }
     }
 
-    fn player_can_all_in(&self) -> bool {
-        let player_idx = self.turn;
-        match self.bets[player_idx] {
-            BetAmount::AllIn => false,
-            BetAmount::In(_) => true,
-            BetAmount::Folded(_) => false,
+    /// `player` will be cashed out once the current hand is over. Until then they play it out like anyone else
+    fn request_leave(&mut self, player: usize) {
+        self.leaving[player] = true;
+        self.sitting_out[player] = true;
+    }
+
+    /// seats a late joiner - right away if it's between hands, once the current hand is over otherwise
+    fn request_seat(&mut self, request: SeatRequest) -> Result<(), ContractError> {
+        ensure!(!self.has_seat_or_request(&request.account_id), ContractError::AlreadyJoined);
+        ensure!(self.num_players() + self.pending_seats.len() < MAX_PLAYERS, ContractError::TableFull);
+        self.config.check_buy_in(request.chips)?;
+
+        // logged before they're seated, so the request comes ahead of the seating in the log
+        GameEvent::SeatRequested { game_id: self.id, account_id: request.account_id.clone(), chips: U128(request.chips) }.emit();
+        self.pending_seats.push(request);
+        if self.between_hands() {
+            self.start_hand(self.hand_keys());
         }
+        Ok(())
     }
 
-    fn player_can_fold(&self) -> bool {
-        let player_idx = self.turn;
-        match self.bets[player_idx] {
-            BetAmount::AllIn => true,
-            BetAmount::In(_) => true,
-            BetAmount::Folded(_) => false,
+    /// cancels a late joiner's request for a seat, returning the chips they bought in with
+    fn cancel_seat_request(&mut self, account_id: &AccountId) -> Option<Balance> {
+        let idx = self.pending_seats.iter().position(|req| &req.account_id == account_id)?;
+        Some(self.pending_seats.remove(idx).chips)
+    }
+
+    /// adds chips to `player`'s stack between hands. Their stack can't go above the table's maximum buy-in, or be left below the minimum
+    fn top_up(&mut self, player: usize, chips: Balance) -> Result<(), ContractError> {
+        ensure!(self.between_hands(), ContractError::NotBetweenHands);
+        let balance = self.table.balances[player] + chips;
+        self.config.check_buy_in(balance)?;
+        self.table.balances[player] = balance;
+        Ok(())
+    }
+
+    /// swaps `player`'s game key for a fresh one between hands. The deck left over from the last hand is encrypted under the old
+    /// aggregate key, so it's thrown out - the aggregate key is recomputed with the new key when the next deck is initialized
+    fn rotate_key(&mut self, player: usize, pk: BnPublicKeyBuf) -> Result<(), ContractError> {
+        ensure!(self.between_hands(), ContractError::NotBetweenHands);
+        self.player_game_pubkeys[player] = pk;
+        self.discard_deck();
+        Ok(())
+    }
+
+    /// the chips owed to players who've left, for the contract to pay out
+    fn take_cash_outs(&mut self) -> Vec<(AccountId, Balance)> {
+        std::mem::take(&mut self.cash_outs)
+    }
+
+    /// the hand can't go on without the stallers' reveal tokens, so it's called off - nobody pays into the pot,
+    /// the stallers' chips are split evenly between everyone else in the hand, and the stallers are removed from the table.
+    /// They're all removed before the next hand is set up, since that can move everyone else to a different seat
+    fn slash_players(&mut self, stallers: &[usize]) -> Result<(), ContractError> {
+        let hand_keys = self.hand_keys();
+        let events = self.table.slash(stallers)?;
+        self.handle_events(events);
+        let mut stallers = stallers.to_vec();
+        stallers.sort_unstable();
+        for &player in stallers.iter().rev() {
+            GameEvent::PlayerLeft {
+                game_id: self.id,
+                account_id: self.player_account_id(player),
+                chips: U128(0),
+                reason: LeaveReason::Slashed,
+            }.emit();
+            self.remove_seat(player);
         }
+        self.table.phase = Phase::SHUFFLE;
+        self.start_hand(hand_keys);
+        Ok(())
     }
 
-    fn player_can_raise(&self) -> bool {
-        let player_idx = self.turn;
-        match self.bets[player_idx] {
-            BetAmount::AllIn => false,
-            BetAmount::In(_) => self.balances[player_idx] > self.ante,
-            BetAmount::Folded(_) => false,
+    /// how long the players the game is waiting on have to act in the current phase
+    fn phase_timeout(&self) -> u64 {
+        match self.table.phase {
+            Phase::SHUFFLE => self.timeouts.shuffle,
+            Phase::DEAL => self.timeouts.deal,
+            Phase::BET0 | Phase::BET1 | Phase::BET2 | Phase::BET3 | Phase::SHOWDOWN | Phase::FINISHED => self.timeouts.bet,
+            Phase::FLOP | Phase::TURN | Phase::RIVER | Phase::SHOWDOWN_REVEAL => self.timeouts.reveal,
         }
     }
 
-    fn next_in_player(&self) -> Option<usize> {
-        let mut count = 0;
-        let mut i = (self.turn + 1) % self.num_players();
-        loop {
-            if !self.player_is_folded(i) {
-                return Some(i);
-            }
-            i = (i + 1) % self.num_players();
-            count += 1;
-            if count == self.num_players() {
-                return None;
-            }
+    fn timed_out(&self, now: u64) -> bool {
+        now >= self.last_modified + self.phase_timeout()
+    }
+
+    /// the players the game is currently waiting on, in seat order
+    fn stallers(&self) -> Vec<usize> {
+        match self.table.phase {
+            // anyone can derive or verify the initial deck, so the only one who can hold up the shuffle is whoever's turn it is
+            Phase::SHUFFLE if self.deck_verified() => vec![self.table.turn],
+            Phase::SHUFFLE => vec![],
+            Phase::DEAL | Phase::FLOP | Phase::TURN | Phase::RIVER | Phase::SHOWDOWN_REVEAL => {
+                (0..self.num_players()).filter(|&i| !self.table.revealed_players[i]).collect()
+            },
+            Phase::BET0 | Phase::BET1 | Phase::BET2 | Phase::BET3 => vec![self.table.turn],
+            Phase::SHOWDOWN | Phase::FINISHED => vec![],
         }
     }
 
-    fn player_is_folded(&self, i: usize) -> bool {
-        matches!(self.bets[i], BetAmount::Folded(_))
+    fn reset_reveal_tokens(&mut self) {
+        self.reveal_tokens_with_proofs = vec![vec![None; self.num_players()]; DECK_SIZE];
     }
 
-    fn transfer_pot(&mut self, winner: usize) {
-        for (loser, bet) in self.bets.iter().enumerate().filter(|(i, _)| *i != winner) {
-            match bet {
-                BetAmount::In(amount) => {
-                    self.balances[winner] += amount;
-                    self.balances[loser] -= amount;
-                }
-                BetAmount::AllIn => {
-                    self.balances[winner] += self.balances[loser];
-                    self.balances[loser] = 0;
-                }
-                BetAmount::Folded(amount) => {
-                    self.balances[winner] += amount;
-                    self.balances[loser] -= amount;
-                }
+    /// the reveal tokens for a card from the players dealt into the hand, in seat order
+    fn hand_reveal_tokens(&self, card_idx: usize) -> impl Iterator<Item = &Option<BnRevealTokenWithProofBuf>> {
+        self.reveal_tokens_with_proofs[card_idx].iter().zip(self.table.dealt_in.iter()).filter(|(_, &dealt_in)| dealt_in).map(|(token, _)| token)
+    }
+
+    fn set_reveal_token(&mut self, card_idx: usize, player_idx: usize, token: BnRevealTokenWithProofBuf) {
+        self.reveal_tokens_with_proofs[card_idx][player_idx] = Some(token);
+    }
+
+    /// `player` shuffles, reveals or bets. The rules live in `rainbase_core` - this just logs what happened,
+    /// and sets up the next hand once the last one is over
+    fn apply(&mut self, player: usize, action: Action) -> Result<(), ContractError> {
+        let events = rainbase_core::apply(&mut self.table, player, action)?;
+        self.handle_events(events);
+        Ok(())
+    }
+
+    fn handle_events(&mut self, events: Vec<Event>) {
+        for event in events {
+            match event {
+                Event::Bet { player, action, amount } => GameEvent::BetPlaced {
+                    game_id: self.id,
+                    account_id: self.player_account_id(player),
+                    action,
+                    amount: U128(amount),
+                }.emit(),
+                Event::PotWon { player, amount } => GameEvent::PotAwarded {
+                    game_id: self.id,
+                    account_id: self.player_account_id(player),
+                    amount: U128(amount),
+                }.emit(),
+                Event::HandOver => self.end_hand(),
             }
         }
     }
 
+    /// the hand is settled - the button moves on to the next player who'll be dealt in, and the table gets ready to shuffle for the next hand
+    fn end_hand(&mut self) {
+        let hand_keys = self.hand_keys();
+        self.table.phase = Phase::SHUFFLE;
+        if let Some(next) = (1..=self.num_players()).map(|k| (self.table.dealer + k) % self.num_players()).find(|&i| !self.sitting_out[i]) {
+            self.table.dealer = next;
+        }
+        self.start_hand(hand_keys);
+    }
+
     fn do_showdown(&mut self, card_mapping: &LookupMap<BnCardBuf, usize>, pp: &BnParameters) {
-        let pks = self.player_game_pubkeys.iter().map(|x| x.deserialize().expect("failed to deserialize pubkey")).collect::<Vec<_>>();
+        let (_, hand_pubkeys) = self.hand_keys();
+        let pks = hand_pubkeys.iter().map(|x| x.deserialize().expect("failed to deserialize pubkey")).collect::<Vec<_>>();
         let mut community = Vec::new();
         for i in (self.num_players() * 2..self.num_players() * 2 + 5) {
             let masked_card = self.deck[i].deserialize().expect("failed to deserialize masked community card");
-            let reveal_tokens_with_proofs = self.reveal_tokens_with_proofs[i].iter().map(|x| {
+            let reveal_tokens_with_proofs = self.hand_reveal_tokens(i).map(|x| {
                 x.as_ref().expect("reveal token not set").deserialize().expect("failed to deserialize reveal token")
             }).collect::<Vec<_>>();
             let decryption_key = reveal_tokens_with_proofs.into_iter().zip(pks.clone()).map(|((token, proof), pk)| (token, proof, pk)).collect();
@@ -400,14 +694,14 @@ impl GameState {
         }
 
         let evaluator = Evaluator::new();
-        let mut hands = Vec::new();
-        for player in (0..self.num_players()).filter(|&i| !self.player_is_folded(i)) {
+        let mut hand_ranks = (0..self.num_players()).map(|_| None).collect::<Vec<_>>();
+        for player in (0..self.num_players()).filter(|&i| !self.table.player_is_folded(i)) {
             let hole_indices = [player * 2, player * 2 + 1];
             let mut hole = Vec::new();
 
             for i in hole_indices {
                 let masked_card = self.deck[i].deserialize().expect("failed to deserialize masked hole card");
-                let reveal_tokens_with_proofs = self.reveal_tokens_with_proofs[i].iter().map(|x| {
+                let reveal_tokens_with_proofs = self.hand_reveal_tokens(i).map(|x| {
                     x.as_ref().expect("reveal token not set").deserialize().expect("failed to deserialize reveal token")
                 }).collect::<Vec<_>>();
                 let decryption_key = reveal_tokens_with_proofs.into_iter().zip(pks.clone()).map(|((token, proof), pk)| (token, proof, pk)).collect();
@@ -417,47 +711,39 @@ impl GameState {
             }
             let hand = [hole, community.clone()].concat();
             let hand_eval = evaluator.evaluate(&hand).expect("failed to evaluate hand");
-            hands.push((player, hand_eval));
+            hand_ranks[player] = Some(hand_eval);
         }
 
-        let &(winner, _) = hands.iter().reduce(|a, b| if a.1 > b.1 { a } else { b }).expect("no winner");
-        self.transfer_pot(winner);
+        let events = self.table.showdown(&hand_ranks);
+        self.handle_events(events);
     }
 
     fn new_round(&mut self) {
-        self.reset_bets();
-        self.reset_checks();
-        self.reset_revealed_players();
+        self.table.new_round();
         self.reset_reveal_tokens();
-        self.ante = 0;
-        self.turn = self.dealer;
     }
 
 }
 
 
-#[derive(BorshDeserialize, BorshSerialize)]
-pub enum Phase {
-    SHUFFLE,
-    DEAL,
-    BLIND,
-    BET0,
-    FLOP,
-    BET1,
-    TURN,
-    BET2,
-    RIVER,
-    BET3,
-    SHOWDOWN_REVEAL,
-    SHOWDOWN,
-}
-
 #[derive(BorshDeserialize, BorshSerialize)]
 pub enum Game {
     WaitingForPlayers(GameLobby),
     InProgress(GameState),
 }
 
+impl Game {
+    /// everyone with a seat at the table, or waiting for one
+    fn player_account_ids(&self) -> Vec<AccountId> {
+        match self {
+            Game::WaitingForPlayers(lobby) => lobby.player_account_ids.clone(),
+            Game::InProgress(state) => {
+                state.player_account_ids.iter().cloned().chain(state.pending_seats.iter().map(|req| req.account_id.clone())).collect()
+            },
+        }
+    }
+}
+
 // this should not be used. for now it's just gonna put an empty buffer. eventually this will panic.
 impl Default for Contract {
     fn default() -> Self {
@@ -467,10 +753,31 @@ impl Default for Contract {
             card_mapping.insert(value, &i);
         }
         Self {
-            games: LookupMap::new(GAMES_STORAGE_KEY),
+            games: GameStore::new(GAMES_STORAGE_KEY),
             trusted_setup_params: BnParamsBuf { buf: vec![] },
             card_mapping,
+            yocto_per_chip: DEFAULT_YOCTO_PER_CHIP,
+            unclaimed: LookupMap::new(UNCLAIMED_STORAGE_KEY),
+        }
+    }
+}
+
+// kept out of the `near_bindgen` block, which can't take closures as arguments
+impl Contract {
+    /// applies the caller's betting action, picked by `action` once the table's been loaded, and pays out anyone it cashes out
+    fn play_action(&mut self, game_id: GameId, action: impl FnOnce(&Table) -> PlayerAction) -> Result<(), ContractError> {
+        let cash_outs = self.games.modify_state(&game_id, |state| {
+            let account_id = env::predecessor_account_id();
+            let player = state.player_index(&account_id).ok_or(ContractError::NotAPlayer)?;
+            let action = action(&state.table);
+            state.apply(player, action.into())?;
+            Ok(state.take_cash_outs())
+        })?;
+
+        for (account_id, chips) in cash_outs {
+            self.cash_out(account_id, chips);
         }
+        Ok(())
     }
 }
 
@@ -479,7 +786,7 @@ impl Default for Contract {
 impl Contract {
     #[init]
     #[private]
-    pub fn init(trusted_setup_params: BnParamsBuf) -> Self {
+    pub fn init(trusted_setup_params: BnParamsBuf, yocto_per_chip: Option<U128>) -> Self {
         let card_values = get_card_elems_buf(52).unwrap();
         let mut card_mapping = LookupMap::new(MAPPING_STORAGE_KEY);
         for (i, value) in card_values.iter().enumerate() {
@@ -487,10 +794,15 @@ impl Contract {
         }
         // check serialization of params
         // let _ = trusted_setup_params.deserialize().expect("failed to deserialize trusted setup params");
+        let yocto_per_chip = yocto_per_chip.map(|rate| rate.0).unwrap_or(DEFAULT_YOCTO_PER_CHIP);
+        assert!(yocto_per_chip > 0, "chips must be worth something");
+
         Self {
-            games: LookupMap::new(GAMES_STORAGE_KEY),
+            games: GameStore::new(GAMES_STORAGE_KEY),
             trusted_setup_params,
-            card_mapping
+            card_mapping,
+            yocto_per_chip,
+            unclaimed: LookupMap::new(UNCLAIMED_STORAGE_KEY),
         }
     }
 
@@ -498,419 +810,876 @@ impl Contract {
         self.trusted_setup_params.clone()
     }
 
-    pub fn get_aggregate_pubkey(&self, game_id: GameId) -> BnPublicKeyBuf {
-        let game = self.games.get(&game_id).expect("game not found");
-        let game = match game {
-            Game::WaitingForPlayers(_) => panic!("game not in progress"),
-            Game::InProgress(game) => game,
-        };
+    pub fn get_yocto_per_chip(&self) -> U128 {
+        U128(self.yocto_per_chip)
+    }
 
-        game.aggregate_pubkey.clone()
+    /// yoctoNEAR owed to `account_id` from refunds that failed to go through
+    pub fn get_unclaimed(&self, account_id: AccountId) -> U128 {
+        U128(self.unclaimed.get(&account_id).unwrap_or(0))
     }
 
-    fn generate_game_id(&self) -> GameId {
-        let seed = env::random_seed();
-        assert!(seed.len() >= 32, "random seed is too short - this should never happen!");
+    /// the aggregate key the deck is masked under. For lobbies, or games waiting for a new deck, this is the key the deck will be masked under if it's initialized now
+    #[handle_result]
+    pub fn get_aggregate_pubkey(&self, game_id: GameId) -> Result<BnPublicKeyBuf, ContractError> {
+        match self.games.load(&game_id)? {
+            Game::WaitingForPlayers(lobby) => Ok(compute_aggregate_pubkey(&self.trusted_setup_params, &lobby.player_account_ids, &lobby.player_game_pubkeys)),
+            Game::InProgress(state) if state.deck.is_empty() => {
+                let (account_ids, pubkeys) = state.hand_keys();
+                Ok(compute_aggregate_pubkey(&self.trusted_setup_params, &account_ids, &pubkeys))
+            },
+            Game::InProgress(state) => Ok(state.aggregate_pubkey),
+        }
+    }
+
+    /// the state of a game in progress, or `None` if there's no such game or it hasn't started yet
+    pub fn get_game(&self, game_id: GameId) -> Option<GameStateView> {
+        match self.games.get(&game_id)? {
+            Game::InProgress(state) => Some(GameStateView::from(&state)),
+            Game::WaitingForPlayers(_) => None,
+        }
+    }
 
-        let mut rng = StdRng::from_seed(seed[0..32].try_into().unwrap());
+    /// the masked deck for a game in progress, for players to shuffle and compute reveal tokens against.
+    /// `None` if there's no such game, it hasn't started yet, or the deck hasn't been initialized
+    pub fn get_deck(&self, game_id: GameId) -> Option<Vec<BnMaskedCardBuf>> {
+        match self.games.get(&game_id)? {
+            Game::InProgress(state) if !state.deck.is_empty() => Some(state.deck),
+            _ => None,
+        }
+    }
+
+    /// the reveal tokens submitted so far for the card at `card_index`, one per seat, for players to unmask their own hole cards with.
+    /// `None` if there's no such game, it hasn't started yet, or there's no such card
+    pub fn get_reveal_tokens(&self, game_id: GameId, card_index: usize) -> Option<Vec<Option<BnRevealTokenWithProofBuf>>> {
+        match self.games.get(&game_id)? {
+            Game::InProgress(state) => state.reveal_tokens_with_proofs.get(card_index).cloned(),
+            Game::WaitingForPlayers(_) => None,
+        }
+    }
 
+    /// a lobby that's waiting for players, or `None` if there's no such lobby or its game has already started
+    pub fn get_lobby(&self, game_id: GameId) -> Option<LobbyView> {
+        match self.games.get(&game_id)? {
+            Game::WaitingForPlayers(lobby) => Some(LobbyView::from(&lobby)),
+            Game::InProgress(_) => None,
+        }
+    }
+
+    /// lobbies that are still accepting players, at most `limit` (default and max 50) starting from `from_index`
+    pub fn list_open_lobbies(&self, from_index: Option<U64>, limit: Option<U64>) -> Vec<LobbyView> {
+        let from_index = from_index.map(|i| i.0).unwrap_or(0);
+        let limit = limit.map(|l| l.0).unwrap_or(MAX_LOBBIES_PER_PAGE).min(MAX_LOBBIES_PER_PAGE);
+        self.games.open_lobbies(from_index as usize, limit as usize)
+            .iter()
+            .filter_map(|game_id| self.get_lobby(*game_id))
+            .collect()
+    }
+
+    /// ids of every lobby and game `account_id` is seated at
+    pub fn get_games_for_account(&self, account_id: AccountId) -> Vec<GameId> {
+        self.games.games_for_account(&account_id)
+    }
+
+    fn generate_game_id(&self) -> GameId {
+        let mut rng = seeded_rng();
+
+        // every game in the store is holding someone's buy-in - finished games and empty lobbies are removed -
+        // so an id is only handed out again once nothing's left in escrow under it
         // TODO: find a more intelligent way to do this
         loop {
             let digits: [u8; 4] = [(); 4].map(|_| rng.gen_range(0..10));
-            if !self.games.contains_key(&digits) {
+            if !self.games.contains(&digits) {
                 return digits;
             }
+        }
+    }
 
-            let game = self.games.get(&digits).unwrap();
-            if let Game::InProgress(ref game_state) = game {
-                if env::block_timestamp() - game_state.last_modified > 3600 * 1000 {
-                    return digits;
-                }
-            }
+    /// converts the attached deposit into chips, refunding any dust that doesn't make up a whole chip
+    fn buy_in(&self) -> Result<Balance, ContractError> {
+        let deposit = env::attached_deposit();
+        let chips = deposit / self.yocto_per_chip;
+        ensure!(chips > 0, ContractError::DepositTooSmall);
 
-            if let Game::WaitingForPlayers(ref lobby) = game {
-                if env::block_timestamp() - lobby.created_at > 20 * 60 * 1000 {
-                    return digits;
-                }
-            }
+        let dust = deposit - chips * self.yocto_per_chip;
+        if dust > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(dust);
+        }
+
+        Ok(chips)
+    }
+
+    /// sends `chips` worth of NEAR back to `account_id`. If the transfer fails, the amount is credited to `unclaimed`
+    fn cash_out(&self, account_id: AccountId, chips: Balance) -> Option<Promise> {
+        if chips == 0 {
+            return None;
+        }
+
+        let amount = chips * self.yocto_per_chip;
+        let promise = Promise::new(account_id.clone())
+            .transfer(amount)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_REFUND_CALLBACK)
+                    .on_refund(account_id, U128(amount))
+            );
+        Some(promise)
+    }
+
+    #[private]
+    pub fn on_refund(&mut self, account_id: AccountId, amount: U128, #[callback_result] result: Result<(), PromiseError>) -> bool {
+        if result.is_ok() {
+            return true;
         }
+
+        log!("refund of {} yoctoNEAR to {} failed, it can be withdrawn with withdraw_unclaimed", amount.0, account_id);
+        let owed = self.unclaimed.get(&account_id).unwrap_or(0);
+        self.unclaimed.insert(&account_id, &(owed + amount.0));
+        false
     }
 
-    pub fn create_game(&mut self, creator_pk: BnPublicKeyBuf, creator_key_ownership_proof: BnZKProofKeyOwnershipBuf) -> GameId {
-        let pk = creator_pk.deserialize().expect("failed to deserialize public key");
-        let proof = creator_key_ownership_proof.deserialize().expect("failed to deserialize key ownership proof");
+    /// retry a refund that previously failed
+    #[handle_result]
+    pub fn withdraw_unclaimed(&mut self) -> Result<Promise, ContractError> {
+        let account_id = env::predecessor_account_id();
+        let amount = self.unclaimed.remove(&account_id).ok_or(ContractError::NothingToWithdraw)?;
+
+        let promise = Promise::new(account_id.clone())
+            .transfer(amount)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_REFUND_CALLBACK)
+                    .on_refund(account_id, U128(amount))
+            );
+        Ok(promise)
+    }
+
+    #[payable]
+    #[handle_result]
+    pub fn create_game(&mut self, creator_pk: BnPublicKeyBuf, creator_key_ownership_proof: BnZKProofKeyOwnershipBuf, timeouts: Option<Timeouts>, config: Option<TableConfig>) -> Result<GameId, ContractError> {
+        let config = config.unwrap_or_default();
+        config.validate()?;
+
+        let pk = creator_pk.deserialize().map_err(|_| ContractError::malformed("public key"))?;
+        let proof = creator_key_ownership_proof.deserialize().map_err(|_| ContractError::malformed("key ownership proof"))?;
         let pp = self.trusted_setup_params.deserialize().expect("failed to deserialize trusted setup params");
         let creator_account_id = env::predecessor_account_id();
         let creator_account_id_bytes = creator_account_id.as_bytes().to_vec();
 
-        BnCardProtocol::verify_key_ownership(&pp, &pk, &creator_account_id_bytes, &proof).expect("failed to verify key ownership proof");
+        BnCardProtocol::verify_key_ownership(&pp, &pk, &creator_account_id_bytes, &proof).map_err(|_| ContractError::InvalidProof(ProofKind::KeyOwnership))?;
 
+        let chips = self.buy_in()?;
+        config.check_buy_in(chips)?;
         let game_id = self.generate_game_id();
-        let lobby = GameLobby::new(game_id, vec![creator_account_id], vec![creator_pk]);
+        let lobby = GameLobby::new(game_id, vec![creator_account_id.clone()], vec![creator_pk], vec![chips], timeouts.unwrap_or_default(), config.clone());
 
-        self.games.insert(&game_id, &Game::WaitingForPlayers(lobby));
-        game_id
+        self.games.commit(&game_id, &Game::WaitingForPlayers(lobby));
+        GameEvent::GameCreated { game_id, creator: creator_account_id, chips: U128(chips), config }.emit();
+        Ok(game_id)
     }
 
-    pub fn join_game(&mut self, game_id: GameId, pk: BnPublicKeyBuf, key_ownership_proof: BnZKProofKeyOwnershipBuf) {
-        assert!(self.games.contains_key(&game_id), "game does not exist");
-
-        let mut game = self.games.get(&game_id).unwrap();
-
-        match game {
-            Game::WaitingForPlayers(ref mut lobby) => {
+    #[payable]
+    #[handle_result]
+    pub fn join_game(&mut self, game_id: GameId, pk: BnPublicKeyBuf, key_ownership_proof: BnZKProofKeyOwnershipBuf) -> Result<(), ContractError> {
+        let chips = self.buy_in()?;
+        self.games.modify(&game_id, |game| match game {
+            Game::WaitingForPlayers(lobby) => {
                 let account_id = env::predecessor_account_id();
-                assert!(lobby.player_account_ids.iter().all(|id| id != &account_id), "already in lobby");
+                ensure!(lobby.player_account_ids.iter().all(|id| id != &account_id), ContractError::AlreadyJoined);
+                ensure!(lobby.num_players() < MAX_PLAYERS, ContractError::TableFull);
+                lobby.config.check_buy_in(chips)?;
 
-                let _pk = pk.deserialize().expect("failed to deserialize public key");
-                let proof = key_ownership_proof.deserialize().expect("failed to deserialize key ownership proof");
+                let _pk = pk.deserialize().map_err(|_| ContractError::malformed("public key"))?;
+                let proof = key_ownership_proof.deserialize().map_err(|_| ContractError::malformed("key ownership proof"))?;
                 let pp = self.trusted_setup_params.deserialize().expect("failed to deserialize trusted setup params");
 
                 let account_id_bytes = account_id.as_bytes().to_vec();
-                BnCardProtocol::verify_key_ownership(&pp, &_pk, &account_id_bytes, &proof).expect("failed to verify key ownership proof");
+                BnCardProtocol::verify_key_ownership(&pp, &_pk, &account_id_bytes, &proof).map_err(|_| ContractError::InvalidProof(ProofKind::KeyOwnership))?;
 
-                lobby.add_player(account_id, pk);
+                lobby.add_player(account_id.clone(), pk, chips);
+                GameEvent::PlayerJoined { game_id, account_id, chips: U128(chips) }.emit();
+                Ok(())
             },
-            _ => panic!("game is no longer accepting for players")
-        }
+            _ => Err(ContractError::GameAlreadyStarted),
+        })
     }
 
     // called once by the game creator to end the lobby
-    pub fn start_game(&mut self, game_id: GameId) {
-        assert!(self.games.contains_key(&game_id), "game does not exist");
-
-        let game = self.games.get(&game_id).unwrap();
+    #[handle_result]
+    pub fn start_game(&mut self, game_id: GameId) -> Result<(), ContractError> {
+        let game = self.games.load(&game_id)?;
 
         match game {
             Game::WaitingForPlayers(lobby) => {
                 let account_id = env::predecessor_account_id();
-                assert!(lobby.player_account_ids[0] == account_id, "only the creator can start the game");
+                ensure!(lobby.player_account_ids[0] == account_id, ContractError::NotCreator);
+                ensure!(lobby.num_players() >= 2, ContractError::NotEnoughPlayers);
 
                 let GameLobby {
                     id: _,
                     player_account_ids,
                     player_game_pubkeys,
+                    balances,
+                    timeouts,
+                    config,
                     created_at: _,
                 } = lobby;
 
-                let state = GameState::new(game_id, player_account_ids, player_game_pubkeys, self.trusted_setup_params.clone());
-                self.games.insert(&game_id, &Game::InProgress(state));
+                let state = GameState::new(game_id, player_account_ids, player_game_pubkeys, balances, timeouts, config, self.trusted_setup_params.clone());
+                GameEvent::GameStarted {
+                    game_id,
+                    players: state.player_account_ids.clone(),
+                    dealer: state.table.dealer,
+                }.emit();
+                self.games.commit(&game_id, &Game::InProgress(state));
+                Ok(())
             },
-            _ => panic!("game is no longer accepting players")
+            _ => Err(ContractError::GameAlreadyStarted),
         }
     }
 
-    // init the deck - game creator calls this once at the beginning
-    // TODO (later): verify the masking proofs. There's a lot of them and it's probably a pain so I'm skipping it for now (oopsies)
-    pub fn init_deck(&mut self, game_id: GameId, deck: Vec<BnMaskedCardBuf>) {
-        assert!(self.games.contains_key(&game_id), "game does not exist");
+    // leave the table and get your chips back as NEAR. Lobbies can be left any time. Leaving a game in the middle of a hand takes effect once
+    // the hand is over - until then you play it out (or time out) like anyone else. If the creator leaves a lobby, the lobby is closed and everyone is refunded
+    #[handle_result]
+    pub fn leave_table(&mut self, game_id: GameId) -> Result<(), ContractError> {
+        let account_id = env::predecessor_account_id();
 
-        let mut game = self.games.get(&game_id).unwrap();
-        match game {
+        let mut game = self.games.load(&game_id)?;
+        let (refunds, cash_outs, close) = match game {
+            Game::WaitingForPlayers(ref mut lobby) => {
+                let player_idx = lobby.player_account_ids.iter().position(|id| id == &account_id).ok_or(ContractError::NotAPlayer)?;
+                if player_idx == 0 {
+                    let refunds = lobby.player_account_ids.iter().cloned().zip(lobby.balances.iter().cloned()).collect();
+                    (refunds, vec![], true)
+                } else {
+                    let chips = lobby.remove_player(player_idx);
+                    (vec![(account_id, chips)], vec![], false)
+                }
+            },
             Game::InProgress(ref mut state) => {
-                let account_id = env::predecessor_account_id();
-                assert!(state.player_account_ids[0] == account_id, "only the creator can init the deck");
-                assert!(state.deck.len() == 0, "deck must not have been initialized yet");
-                assert!(deck.len() == 52, "deck must have 52 cards");
-
-                state.new_round();
-                state.set_deck(deck)
+                state.last_modified = env::block_timestamp();
+                if let Some(chips) = state.cancel_seat_request(&account_id) {
+                    (vec![(account_id, chips)], vec![], false)
+                } else {
+                    let player_idx = state.player_index(&account_id).ok_or(ContractError::NotAPlayer)?;
+                    if state.between_hands() {
+                        // if that leaves one player, they win what's left and the game is over
+                        let chips = state.remove_player(player_idx)?;
+                        (vec![(account_id, chips)], state.take_cash_outs(), state.finished())
+                    } else {
+                        // they're cashed out once the hand is over
+                        state.request_leave(player_idx);
+                        (vec![], vec![], false)
+                    }
+                }
             },
-            _ => panic!("game is not in progress")
+        };
+
+        if close {
+            self.games.remove(&game_id);
+        } else {
+            self.games.commit(&game_id, &game);
         }
-    }
 
-    // shuffle the deck - each player calls this going around one at a time in turn order - the dealer calls this first
-    pub fn shuffle_deck(&mut self, game_id: GameId, shuffle: BnShuffleOutputBuf) {
-        assert!(self.games.contains_key(&game_id), "game does not exist");
+        for (account_id, chips) in refunds {
+            GameEvent::PlayerLeft { game_id, account_id: account_id.clone(), chips: U128(chips), reason: LeaveReason::Left }.emit();
+            self.cash_out(account_id, chips);
+        }
+        for (account_id, chips) in cash_outs {
+            self.cash_out(account_id, chips);
+        }
+        Ok(())
+    }
 
-        let mut game = self.games.get(&game_id).unwrap();
-        match game {
-            Game::InProgress(ref mut state) => {
-                let account_id = env::predecessor_account_id();
-                assert!(state.player_account_ids.contains(&account_id), "only players can shuffle the deck");
+    // replace your game key with a fresh one for the next hand, so a leaked secret key only ever exposes the hands it was used for.
+    // this is optional, and only allowed between hands - after the last hand ends and before anyone shuffles. If someone's already
+    // initialized the deck for the next hand, it's thrown out and has to be initialized again under the new aggregate key
+    #[handle_result]
+    pub fn rotate_key(&mut self, game_id: GameId, pk: BnPublicKeyBuf, key_ownership_proof: BnZKProofKeyOwnershipBuf) -> Result<(), ContractError> {
+        let account_id = env::predecessor_account_id();
+        self.games.modify_state(&game_id, |state| {
+            let player = state.player_index(&account_id).ok_or(ContractError::NotAPlayer)?;
+
+            let _pk = pk.deserialize().map_err(|_| ContractError::malformed("public key"))?;
+            let proof = key_ownership_proof.deserialize().map_err(|_| ContractError::malformed("key ownership proof"))?;
+            let pp = self.trusted_setup_params.deserialize().expect("failed to deserialize trusted setup params");
+
+            let account_id_bytes = account_id.as_bytes().to_vec();
+            BnCardProtocol::verify_key_ownership(&pp, &_pk, &account_id_bytes, &proof).map_err(|_| ContractError::InvalidProof(ProofKind::KeyOwnership))?;
+
+            state.rotate_key(player, pk)?;
+            GameEvent::KeyRotated { game_id, account_id: account_id.clone() }.emit();
+            Ok(())
+        })
+    }
 
-                let player_index = state.player_account_ids.iter().position(|id| id == &account_id).unwrap();
-                assert!(state.turn == player_index, "it is not your turn to shuffle the deck");
+    // stop being dealt in. Takes effect right away between hands, otherwise from the next hand on - the current one is played out as usual
+    #[handle_result]
+    pub fn sit_out(&mut self, game_id: GameId) -> Result<(), ContractError> {
+        let account_id = env::predecessor_account_id();
+        self.games.modify_state(&game_id, |state| {
+            let player = state.player_index(&account_id).ok_or(ContractError::NotAPlayer)?;
+            state.sit_out(player);
+            GameEvent::PlayerSatOut { game_id, account_id: account_id.clone() }.emit();
+            Ok(())
+        })
+    }
 
-                let (deck, proof) = shuffle.deserialize().expect("failed to deserialize shuffle");
-                let pp = self.trusted_setup_params.deserialize().expect("failed to deserialize trusted setup params");
-                let aggregate_pubkey = state.aggregate_pubkey.deserialize().expect("failed to deserialize aggregate pubkey");
-                let mut old_deck = Vec::new();
-                for card in state.deck.iter() {
-                    old_deck.push(card.deserialize().expect("failed to deserialize card"));
-                }
+    // start being dealt in again (from the next hand, if one's in progress). This also takes back a request to leave
+    #[handle_result]
+    pub fn sit_in(&mut self, game_id: GameId) -> Result<(), ContractError> {
+        let account_id = env::predecessor_account_id();
+        self.games.modify_state(&game_id, |state| {
+            let player = state.player_index(&account_id).ok_or(ContractError::NotAPlayer)?;
+            state.sit_in(player);
+            GameEvent::PlayerSatIn { game_id, account_id: account_id.clone() }.emit();
+            Ok(())
+        })
+    }
 
-                BnCardProtocol::verify_shuffle(&pp, &aggregate_pubkey, &old_deck, &deck, &proof).expect("failed to verify shuffle proof");
+    // add chips to your stack between hands, paid for with the attached deposit like a buy-in. Your stack has to end up within the table's buy-in range.
+    // players who run out of chips are eliminated at the end of the hand - they can rebuy with `request_seat`
+    #[payable]
+    #[handle_result]
+    pub fn top_up(&mut self, game_id: GameId) -> Result<(), ContractError> {
+        let account_id = env::predecessor_account_id();
+        let chips = self.buy_in()?;
+        self.games.modify_state(&game_id, |state| {
+            let player = state.player_index(&account_id).ok_or(ContractError::NotAPlayer)?;
+            state.top_up(player, chips)?;
+            GameEvent::ChipsAdded { game_id, account_id: account_id.clone(), chips: U128(chips), balance: U128(state.table.balances[player]) }.emit();
+            Ok(())
+        })
+    }
 
-                let mut shuffled_deck = Vec::new();
-                for card in deck {
-                    let card = BnMaskedCardBuf::serialize(card).expect("failed to serialize masked card");
-                    shuffled_deck.push(card);
-                }
-                state.set_deck(shuffled_deck);
-                state.reset_reveal_tokens();
-                state.turn = (state.turn + 1) % state.num_players();
+    // ask for a seat at a game that's already started, buying in with the attached deposit. You're seated right away between hands,
+    // otherwise once the current hand is over. Leaving the table before then takes the request back and refunds the buy-in
+    #[payable]
+    #[handle_result]
+    pub fn request_seat(&mut self, game_id: GameId, pk: BnPublicKeyBuf, key_ownership_proof: BnZKProofKeyOwnershipBuf) -> Result<(), ContractError> {
+        let account_id = env::predecessor_account_id();
+        let chips = self.buy_in()?;
+        self.games.modify_state(&game_id, |state| {
+            let _pk = pk.deserialize().map_err(|_| ContractError::malformed("public key"))?;
+            let proof = key_ownership_proof.deserialize().map_err(|_| ContractError::malformed("key ownership proof"))?;
+            let pp = self.trusted_setup_params.deserialize().expect("failed to deserialize trusted setup params");
+
+            let account_id_bytes = account_id.as_bytes().to_vec();
+            BnCardProtocol::verify_key_ownership(&pp, &_pk, &account_id_bytes, &proof).map_err(|_| ContractError::InvalidProof(ProofKind::KeyOwnership))?;
+
+            state.request_seat(SeatRequest { account_id: account_id.clone(), game_pubkey: pk, chips })
+        })
+    }
 
-                if state.turn == state.dealer {
-                    state.phase = Phase::DEAL;
-                }
-            },
-            _ => panic!("game is not in progress")
+    // once the deadline for the current phase has passed, any player the game isn't waiting on can call this to move it along.
+    // players holding up the betting are folded, a player holding up the shuffle is sent home with their chips,
+    // and players withholding reveal tokens have their chips split between everyone else, since the hand can't go on without them
+    #[handle_result]
+    pub fn claim_timeout(&mut self, game_id: GameId) -> Result<(), ContractError> {
+        let account_id = env::predecessor_account_id();
+        let refunds = self.games.modify_state(&game_id, |state| {
+            let claimant = state.player_index(&account_id).ok_or(ContractError::NotAPlayer)?;
+            ensure!(state.timed_out(env::block_timestamp()), ContractError::DeadlineNotPassed);
+
+            let stallers = state.stallers();
+            ensure!(!stallers.is_empty(), ContractError::NotWaitingOnAnyone);
+            ensure!(!stallers.contains(&claimant), ContractError::WaitingOnYou);
+
+            let mut refunds = match state.table.phase {
+                Phase::BET0 | Phase::BET1 | Phase::BET2 | Phase::BET3 => {
+                    log!("{} timed out and was folded", state.player_account_id(stallers[0]));
+                    state.apply(stallers[0], Action::Fold)?;
+                    vec![]
+                },
+                Phase::SHUFFLE => {
+                    let staller_account_id = state.player_account_id(stallers[0]);
+                    log!("{} timed out shuffling and was removed from the table", staller_account_id);
+                    let chips = state.eject_player(stallers[0]);
+                    GameEvent::PlayerLeft {
+                        game_id,
+                        account_id: staller_account_id.clone(),
+                        chips: U128(chips),
+                        reason: LeaveReason::TimedOut,
+                    }.emit();
+                    vec![(staller_account_id, chips)]
+                },
+                _ => {
+                    for &staller in stallers.iter() {
+                        log!("{} timed out revealing and was slashed", state.player_account_id(staller));
+                    }
+                    state.slash_players(&stallers)?;
+                    vec![]
+                },
+            };
+            refunds.extend(state.take_cash_outs());
+            Ok(refunds)
+        })?;
+
+        for (account_id, chips) in refunds {
+            self.cash_out(account_id, chips);
         }
+        Ok(())
+    }
+
+    // init the deck - game creator calls this once at the beginning with the canonical deck masked under the aggregate key,
+    // along with a masking proof for each card. The first batch of proofs is checked right away, the rest via `verify_initial_deck`
+    #[handle_result]
+    pub fn init_deck(&mut self, game_id: GameId, deck: Vec<BnMaskedCardBuf>, masking_proofs: Vec<BnZKProofMaskingBuf>) -> Result<bool, ContractError> {
+        self.games.modify_state(&game_id, |state| {
+            let account_id = env::predecessor_account_id();
+            ensure!(state.player_account_ids[0] == account_id, ContractError::NotCreator);
+            ensure!(state.deck.len() == 0, ContractError::DeckAlreadyInitialized);
+            ensure!(deck.len() == DECK_SIZE, ContractError::WrongDeckSize { expected: DECK_SIZE, got: deck.len() });
+            ensure!(masking_proofs.len() == deck.len(), ContractError::MissingMaskingProofs);
+            ensure!(state.table.num_dealt_in() >= 2, ContractError::NotEnoughPlayers);
+
+            let pp = self.trusted_setup_params.deserialize().expect("failed to deserialize trusted setup params");
+
+            state.new_round();
+            state.refresh_aggregate_pubkey();
+            state.set_deck(deck);
+            state.initial_deck_proofs = masking_proofs;
+            state.num_cards_verified = 0;
+            Ok(state.verify_initial_deck_batch(&pp))
+        })
+    }
+
+    // derive the initial deck on-chain from the canonical card values, so nobody has to be trusted to submit it.
+    // any player can call this once the game has started (or after someone leaves and the deck is thrown out)
+    #[handle_result]
+    pub fn derive_initial_deck(&mut self, game_id: GameId) -> Result<(), ContractError> {
+        self.games.modify_state(&game_id, |state| {
+            let account_id = env::predecessor_account_id();
+            ensure!(state.player_account_ids.contains(&account_id), ContractError::NotAPlayer);
+            ensure!(state.deck.len() == 0, ContractError::DeckAlreadyInitialized);
+            ensure!(state.table.num_dealt_in() >= 2, ContractError::NotEnoughPlayers);
+
+            let pp = self.trusted_setup_params.deserialize().expect("failed to deserialize trusted setup params");
+
+            state.new_round();
+            state.refresh_aggregate_pubkey();
+            state.derive_initial_deck(&pp);
+            Ok(())
+        })
+    }
+
+    // verify the next batch of masking proofs for the initial deck - any player can call this until the whole deck is verified.
+    // returns false if the batch was invalid, in which case the deck is discarded and has to be initialized again
+    #[handle_result]
+    pub fn verify_initial_deck(&mut self, game_id: GameId) -> Result<bool, ContractError> {
+        self.games.modify_state(&game_id, |state| {
+            let account_id = env::predecessor_account_id();
+            ensure!(state.player_account_ids.contains(&account_id), ContractError::NotAPlayer);
+            ensure!(!state.deck.is_empty(), ContractError::DeckNotInitialized);
+            ensure!(!state.deck_verified(), ContractError::DeckAlreadyVerified);
+
+            let pp = self.trusted_setup_params.deserialize().expect("failed to deserialize trusted setup params");
+            Ok(state.verify_initial_deck_batch(&pp))
+        })
+    }
+
+    // shuffle the deck - each player dealt into the hand calls this going around one at a time in turn order - the dealer calls this first
+    #[handle_result]
+    pub fn shuffle_deck(&mut self, game_id: GameId, shuffle: BnShuffleOutputBuf) -> Result<(), ContractError> {
+        self.games.modify_state(&game_id, |state| {
+            let account_id = env::predecessor_account_id();
+            let player_index = state.player_index(&account_id).ok_or(ContractError::NotAPlayer)?;
+            state.table.check(player_index, Action::Shuffle)?;
+            ensure!(state.deck_verified(), ContractError::DeckNotVerified);
+
+            let (deck, proof) = shuffle.deserialize().map_err(|_| ContractError::malformed("shuffle"))?;
+            let pp = self.trusted_setup_params.deserialize().expect("failed to deserialize trusted setup params");
+            let aggregate_pubkey = state.aggregate_pubkey.deserialize().expect("failed to deserialize aggregate pubkey");
+            let mut old_deck = Vec::new();
+            for card in state.deck.iter() {
+                old_deck.push(card.deserialize().expect("failed to deserialize card"));
+            }
+
+            BnCardProtocol::verify_shuffle(&pp, &aggregate_pubkey, &old_deck, &deck, &proof).map_err(|_| ContractError::InvalidProof(ProofKind::Shuffle))?;
+
+            let mut shuffled_deck = Vec::new();
+            for card in deck {
+                let card = BnMaskedCardBuf::serialize(card).expect("failed to serialize masked card");
+                shuffled_deck.push(card);
+            }
+            state.set_deck(shuffled_deck);
+            state.reset_reveal_tokens();
+            GameEvent::DeckShuffled { game_id, account_id }.emit();
+            state.apply(player_index, Action::Shuffle)
+        })
     }
 
     // deal everyone their two cards - each player has to call (any order) this with their reveal tokens calculated client-side.
-    pub fn deal(&mut self, game_id: GameId, card_indices: Vec<usize>, reveal_tokens_with_proofs: Vec<BnRevealTokenWithProofBuf>) {
-        assert!(self.games.contains_key(&game_id), "game does not exist");
+    // once everyone has, the antes and blinds are posted automatically and the first betting round starts
+    #[handle_result]
+    pub fn deal(&mut self, game_id: GameId, card_indices: Vec<usize>, reveal_tokens_with_proofs: Vec<BnRevealTokenWithProofBuf>) -> Result<(), ContractError> {
+        self.games.modify_state(&game_id, |state| {
+            ensure!(matches!(state.table.phase, Phase::DEAL), ContractError::WrongPhase(state.table.phase.clone()));
+            let account_id = env::predecessor_account_id();
+            let player_index = state.player_index(&account_id).ok_or(ContractError::NotAPlayer)?;
+
+            state.table.check(player_index, Action::Reveal)?;
+
+            // player at idx i gets revealed 2*i, 2*i+1
+            // => player at idx i should reveal every other dealt in player's cards but those
+            let expected_card_indices = card_indices::deal_indices(&state.table.dealt_in, player_index);
+            card_indices::check_card_indices(&card_indices, reveal_tokens_with_proofs.len(), &expected_card_indices)?;
+
+            let pp = self.trusted_setup_params.deserialize().expect("failed to deserialize trusted setup params");
+            let pk = state.player_game_pubkeys[player_index].deserialize().expect("failed to deserialize player pubkey");
+
+            for (&card_idx, reveal_token_with_proof) in card_indices.iter().zip(reveal_tokens_with_proofs) {
+                let (reveal_token, proof) = reveal_token_with_proof.deserialize().map_err(|_| ContractError::malformed("reveal token"))?;
+                let masked_card = state.deck[card_idx].deserialize().expect("failed to deserialize masked card");
+                BnCardProtocol::verify_reveal(&pp, &pk, &reveal_token, &masked_card, &proof).map_err(|_| ContractError::InvalidProof(ProofKind::Reveal))?;
+                state.set_reveal_token(card_idx, player_index, reveal_token_with_proof);
+            }
+            GameEvent::TokensRevealed { game_id, account_id, card_indices }.emit();
 
-        let mut game = self.games.get(&game_id).unwrap();
-        match game {
-            Game::InProgress(ref mut state) => {
-                assert!(matches!(state.phase, Phase::DEAL), "game is not in the deal phase");
-                let account_id = env::predecessor_account_id();
-                assert!(state.player_account_ids.contains(&account_id), "only players can deal");
-
-                let player_index = state.player_account_ids.iter().position(|id| id == &account_id).unwrap();
-
-                // player at idx i gets revealed 2*i, 2*i+1
-                // => player at idx i should reveal every card but those cards
-                // TODO checks later
-                // assert!(card_indices.len() == reveal_tokens_with_proofs.len(), "card indices and reveal tokens with proofs should have same len");
-                // assert!(card_indices.iter().is_sorted(), "card indices must be sorted");
-                // let expected_card_indices = (0..2*state.num_players()).filter(|&i| player_index * 2 != i && player_index * 2 + 1 != i).collect::<Vec<usize>>();
-                // assert!(card_indices.len() == expected_card_indices.len(), "you must reveal the correct number of cards");
-                // assert!(card_indices.iter().all(|i| expected_card_indices.contains(i)), "you must reveal the correct cards");
-                // let prededup_len = card_indices.len();
-                // card_indices.dedup();
-                // assert!(card_indices.len() == prededup_len, "card indices cannot have duplicates");
+            state.apply(player_index, Action::Reveal)
+        })
+    }
 
-                let pp = self.trusted_setup_params.deserialize().expect("failed to deserialize trusted setup params");
-                let pk = state.player_game_pubkeys[player_index].deserialize().expect("failed to deserialize player pubkey");
+    // act when it's your turn to bet - players call this in turn order until the betting is done. this is only called during the bet phases
+    #[handle_result]
+    pub fn act(&mut self, game_id: GameId, action: PlayerAction) -> Result<(), ContractError> {
+        self.play_action(game_id, |_| action)
+    }
 
-                for (card_idx, reveal_token_with_proof) in card_indices.into_iter().zip(reveal_tokens_with_proofs) {
-                    let (reveal_token, proof) = reveal_token_with_proof.deserialize().expect("failed to deserialize reveal token with proof");
-                    let masked_card = state.deck[card_idx].deserialize().expect("failed to deserialize masked card");
-                    BnCardProtocol::verify_reveal(&pp, &pk, &reveal_token, &masked_card, &proof).expect("failed to verify reveal token proof");
-                    state.set_reveal_token(card_idx, player_index, reveal_token_with_proof);
-                }
+    // place bet - same as `act`, for clients that still pass the action as flags. exactly one of them has to be set.
+    // `raise` opens the betting too when there's nothing to call, like it did before bets and raises were told apart
+    #[handle_result]
+    pub fn bet(&mut self, game_id: GameId, call: bool, check: bool, all_in: bool, fold: bool, raise: Option<Balance>) -> Result<(), ContractError> {
+        let action = PlayerAction::from_flags(call, check, all_in, fold, raise).ok_or(ContractError::InvalidBetFlags)?;
+        self.play_action(game_id, |table| action.opening_if_unopened(table))
+    }
 
-                state.set_revealed_player(player_index);
+    // reveal cards - each player has to call this (any order) with their reveal tokens calculated client side. number of cards revealed depends on the phase
+    #[handle_result]
+    pub fn reveal(&mut self, game_id: GameId, card_indices: Vec<usize>, reveal_tokens_with_proofs: Vec<BnRevealTokenWithProofBuf>) -> Result<(), ContractError> {
+        let cash_outs = self.games.modify_state(&game_id, |state| {
+            // the phase comes first - during the betting rounds everyone's still marked as having revealed the last street
+            ensure!(matches!(state.table.phase, Phase::FLOP | Phase::TURN | Phase::RIVER | Phase::SHOWDOWN_REVEAL), ContractError::WrongPhase(state.table.phase.clone()));
+            let account_id = env::predecessor_account_id();
+            let player = state.player_index(&account_id).ok_or(ContractError::NotAPlayer)?;
+            state.table.check(player, Action::Reveal)?;
+            let expected_card_indices = card_indices::reveal_indices(&state.table.phase, state.num_players(), player)?;
+            card_indices::check_card_indices(&card_indices, reveal_tokens_with_proofs.len(), &expected_card_indices)?;
+
+            let pp = self.trusted_setup_params.deserialize().expect("failed to deserialize trusted setup params");
+            let pk = state.player_game_pubkeys[player].deserialize().expect("failed to deserialize player pubkey");
+
+            for (&card_idx, reveal_token_with_proof) in card_indices.iter().zip(reveal_tokens_with_proofs) {
+                let masked_card = state.deck[card_idx].deserialize().expect("failed to deserialize masked card");
+                let (reveal_token, proof) = reveal_token_with_proof.deserialize().map_err(|_| ContractError::malformed("reveal token"))?;
+                BnCardProtocol::verify_reveal(&pp, &pk, &reveal_token, &masked_card, &proof).map_err(|_| ContractError::InvalidProof(ProofKind::Reveal))?;
+                state.set_reveal_token(card_idx, player, reveal_token_with_proof);
+            }
+            GameEvent::TokensRevealed { game_id, account_id, card_indices }.emit();
 
-                if state.all_players_revealed() {
-                    state.phase = Phase::BLIND;
-                    state.turn = (state.dealer + 1) % state.num_players();
-                }
-            },
-            _ => panic!("game is not in progress")
+            state.apply(player, Action::Reveal)?;
+            if let Phase::SHOWDOWN = state.table.phase {
+                state.do_showdown(&self.card_mapping, &pp);
+            }
+            Ok(state.take_cash_outs())
+        })?;
+
+        for (account_id, chips) in cash_outs {
+            self.cash_out(account_id, chips);
         }
+        Ok(())
     }
+}
 
-    // blind
-    pub fn blind(&mut self, game_id: GameId) {
-        assert!(self.games.contains_key(&game_id), "game does not exist");
+/*
+ * The rest of this file holds the inline tests for the code above
+ * Learn more about Rust tests: https://doc.rust-lang.org/book/ch11-01-writing-tests.html
+ */
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use PlayerAction::*;
+
+    /// a table waiting for everyone to deal, with the button on seat 0. The rules don't touch the cryptography, so it's all left empty
+    fn table(balances: &[Balance]) -> GameState {
+        let num_players = balances.len();
+        let mut table = Table::new(balances.to_vec(), TableConfig::default().stakes());
+        table.phase = Phase::DEAL;
+        GameState {
+            id: [0; 4],
+            player_account_ids: (0..num_players).map(|i| format!("player{}.near", i).parse().unwrap()).collect(),
+            table,
+            sitting_out: vec![false; num_players],
+            leaving: vec![false; num_players],
+            pending_seats: vec![],
+            cash_outs: vec![],
+            last_modified: 0,
+            timeouts: Timeouts::default(),
+            config: TableConfig::default(),
+            pp: BnParamsBuf { buf: vec![] },
+            player_game_pubkeys: vec![BnPublicKeyBuf { buf: vec![] }; num_players],
+            aggregate_pubkey: BnPublicKeyBuf { buf: vec![] },
+            deck: vec![],
+            reveal_tokens_with_proofs: vec![vec![None; num_players]; DECK_SIZE],
+            initial_deck_proofs: vec![],
+            num_cards_verified: 0,
+        }
+    }
 
-        let mut game = self.games.get(&game_id).unwrap();
-        match game {
-            Game::InProgress(ref mut state) => {
-                assert!(matches!(state.phase, Phase::BLIND), "game is not in the blind phase");
-                let account_id = env::predecessor_account_id();
-                assert!(state.player_account_ids.contains(&account_id), "only players can blind");
+    /// a table that's just posted its blinds
+    fn preflop(balances: &[Balance]) -> GameState {
+        let mut state = table(balances);
+        reveal_all(&mut state);
+        state
+    }
 
-                let player_index = state.player_account_ids.iter().position(|id| id == &account_id).unwrap();
-                assert!(state.turn == player_index, "it is not your turn to blind");
+    /// a table waiting for the next hand to be shuffled, with everyone who has chips dealt in
+    fn between_hands(balances: &[Balance]) -> GameState {
+        let mut state = table(balances);
+        state.table.phase = Phase::SHUFFLE;
+        state.start_hand(state.hand_keys());
+        state
+    }
 
-                let player_balance = state.balances[player_index];
-                let blind_amount = if state.turn == (state.dealer + 1) % state.num_players() { LITTLE_BLIND_AMOUNT } else { BIG_BLIND_AMOUNT };
-                if player_balance < blind_amount {
-                    state.bets[player_index] = BetAmount::AllIn;
-                    state.ante = state.balances[player_index];
-                } else {
-                    state.bets[player_index] = BetAmount::In(blind_amount);
-                    state.ante = blind_amount;
-                }
+    /// deals the hand a table set up by `between_hands` and posts the blinds, skipping the cryptography
+    fn deal_hand(state: &mut GameState) {
+        state.table.phase = Phase::DEAL;
+        reveal_all(state);
+    }
 
-                if state.turn == (state.dealer + 3) % state.num_players() {
-                    state.phase = Phase::BET0;
-                }
-            },
-            _ => panic!("game is not in progress")
-        } 
+    /// everyone who still has to reveal does, without any reveal tokens
+    fn reveal_all(state: &mut GameState) {
+        for player in 0..state.num_players() {
+            if !state.table.revealed_players[player] {
+                state.apply(player, Action::Reveal).unwrap();
+            }
+        }
     }
 
+    fn seat_request(name: &str, chips: Balance) -> SeatRequest {
+        SeatRequest { account_id: name.parse().unwrap(), game_pubkey: BnPublicKeyBuf { buf: vec![] }, chips }
+    }
 
-    // place bet - players call this in turn order until the betting is done. this is only called during the bet phases
-    pub fn bet(&mut self, game_id: GameId, call: bool, check: bool, all_in: bool, fold: bool, raise: Option<Balance>) {
-        assert!(self.games.contains_key(&game_id), "game does not exist");
+    /// plays out `actions` in order, each of which has to be legal for the player whose turn it is
+    fn play(state: &mut GameState, actions: &[(usize, PlayerAction)]) {
+        for &(player, action) in actions {
+            assert_eq!(state.table.turn, player, "expected it to be player {}'s turn to {:?}", player, action);
+            state.apply(player, action.into()).unwrap_or_else(|err| panic!("player {} couldn't {:?}: {}", player, action, err));
+        }
+    }
 
-        let mut game = self.games.get(&game_id).unwrap();
-        match game {
-            Game::InProgress(ref mut state) => {
-                assert!(matches!(state.phase, Phase::BET0 | Phase::BET1 | Phase::BET2 | Phase::BET3), "game is not in a bet phase");
+    #[test]
+    fn fold_win_moves_the_button() {
+        let mut state = preflop(&[1000; 4]);
+        play(&mut state, &[(3, Fold), (0, Fold), (1, Fold)]);
+        assert_eq!(state.table.phase, Phase::SHUFFLE);
+        assert_eq!(state.table.dealer, 1);
+        assert_eq!(state.table.turn, 1);
+        assert_eq!(state.table.contributions(), vec![0; 4]);
+
+        // heads-up, it goes to the other player, who posts the small blind next hand
+        let mut state = preflop(&[1000, 1000]);
+        play(&mut state, &[(0, Fold)]);
+        assert_eq!(state.table.dealer, 1);
+        assert_eq!(state.table.small_blind_seat(), 1);
+        assert_eq!(state.table.big_blind_seat(), 0);
+    }
 
-                let account_id = env::predecessor_account_id();
-                assert!(state.player_account_ids.contains(&account_id), "only players can bet");
-                let player = state.player_account_ids.iter().position(|id| id == &account_id).unwrap();
-                assert!(state.turn == player, "it is not your turn to bet");
-
-                match (call, check, all_in, fold, raise) {
-                    // call
-                    (true, false, false, false, None) => {
-                        assert!(state.player_can_call(), "you cannot call");
-                        state.bets[player] = BetAmount::In(state.ante);
-                        state.reset_checks();
-                    }
-                    // check
-                    (false, true, false, false, None) => {
-                        assert!(state.player_can_check(), "you cannot check");
-                        state.set_player_checked(player);
-                    }
-                    // all in
-                    (false, false, true, false, None) => {
-                        assert!(state.player_can_all_in(), "you cannot all in");
-                        state.bets[player] = BetAmount::AllIn;
-                        if state.balances[player] > state.ante {
-                            state.ante = state.balances[player];
-                        }
-                        state.reset_checks()
-                    }
-                    // fold
-                    (false, false, false, true, None) => {
-                        assert!(state.player_can_fold(), "you cannot fold");
-                        state.bets[player] = match state.bets[player] {
-                            BetAmount::In(amount) => BetAmount::Folded(amount),
-                            BetAmount::AllIn => BetAmount::Folded(state.balances[player]),
-                            _ => unreachable!()
-                        };
-                        state.checks[player] = false;
-                    }
-                    // raise
-                    (false, false, false, false, Some(raise_amount)) => {
-                        assert!(state.player_can_raise(), "you cannot raise");
-                        assert!(raise_amount > state.ante, "raise amount must be greater than the ante");
-                        assert!(raise_amount <= state.balances[player], "raise amount must be less than or equal to your balance");
-                        state.bets[player] = BetAmount::In(raise_amount); 
-                        state.ante = raise_amount;
-                        state.reset_checks();
-                    }
-                    _ => panic!("invalid bet flags")
-                };
-
-                if state.num_players_in() == 1 {
-                    // that player won  
-                    let winner = state.next_in_player().unwrap();
-                    state.transfer_pot(winner);
-                    state.phase = Phase::SHUFFLE;
-                    state.reset_bets();
-                    state.reset_checks();
-                    state.reset_revealed_players();
-                } else if state.enough_players_checked() {
-                    // move to next phase
-                    state.phase = match state.phase {
-                        Phase::BET0 => Phase::FLOP,
-                        Phase::BET1 => Phase::TURN,
-                        Phase::BET2 => Phase::RIVER,
-                        Phase::BET3 => Phase::SHOWDOWN_REVEAL,
-                        _ => unreachable!()
-                    };
-                    state.reset_checks();
-                } else {
-                    // move to next player
-                    state.turn = state.next_in_player().unwrap();
-                }
+    #[test]
+    fn bet_flags_open_a_postflop_street() {
+        let mut state = preflop(&[1000; 3]);
+        play(&mut state, &[(0, Call), (1, Call), (2, Check)]);
+        reveal_all(&mut state);
+        assert_eq!(state.table.phase, Phase::BET1);
+
+        // with nothing to call, `raise` opens the betting, and after that it raises
+        let open = PlayerAction::from_flags(false, false, false, false, Some(20)).unwrap().opening_if_unopened(&state.table);
+        assert_eq!(open, Bet(U128(20)));
+        play(&mut state, &[(1, open)]);
+        let raise = PlayerAction::from_flags(false, false, false, false, Some(40)).unwrap().opening_if_unopened(&state.table);
+        assert_eq!(raise, Raise { to: U128(40) });
+        play(&mut state, &[(2, raise)]);
+        assert_eq!(state.table.current_bet, 40);
+    }
 
-                state.turn = state.next_in_player().expect("next player should exist");
-            },
-            _ => panic!("game is not in progress")
+    #[test]
+    fn players_sitting_out_are_skipped() {
+        let mut state = between_hands(&[1000; 4]);
+        state.sit_out(1);
+        assert_eq!(state.table.dealt_in, vec![true, false, true, true]);
+        assert_eq!(state.table.revealed_players, vec![false, true, false, false]);
+
+        // the blinds skip seat 1, and so does the action
+        deal_hand(&mut state);
+        assert_eq!(state.table.contributions(), vec![0, 0, 5, 10]);
+        assert!(state.table.player_is_folded(1));
+        assert_eq!(state.table.turn, 0);
+        play(&mut state, &[(0, Call), (2, Call), (3, Check)]);
+        assert_eq!(state.table.phase, Phase::FLOP);
+        assert_eq!(state.table.revealed_players, vec![false, true, false, false]);
+
+        reveal_all(&mut state);
+        play(&mut state, &[(2, Check), (3, Check), (0, Check)]);
+        reveal_all(&mut state);
+        play(&mut state, &[(2, Bet(U128(10))), (3, Fold), (0, Fold)]);
+
+        // the button skips them too
+        assert_eq!(state.table.phase, Phase::SHUFFLE);
+        assert_eq!(state.table.dealer, 2);
+        assert_eq!(state.table.balances, vec![990, 1000, 1020, 990]);
+
+        // sitting out mid-hand takes effect from the next hand, and sitting back in between hands deals them right back in
+        state.sit_in(1);
+        assert_eq!(state.table.dealt_in, vec![true; 4]);
+        deal_hand(&mut state);
+        state.sit_out(0);
+        assert!(state.table.dealt_in[0]);
+    }
+
+    #[test]
+    fn leaving_mid_hand_cashes_out_once_the_hand_is_over() {
+        let mut state = preflop(&[1000; 4]);
+        state.request_leave(1);
+        state.request_leave(3);
+        assert!(state.take_cash_outs().is_empty());
+
+        // they still play the hand out
+        play(&mut state, &[(3, Fold), (0, Fold), (1, Fold)]);
+        assert_eq!(state.table.phase, Phase::SHUFFLE);
+        assert_eq!(state.player_account_ids, vec!["player0.near".parse().unwrap(), "player2.near".parse::<AccountId>().unwrap()]);
+        assert_eq!(state.table.balances, vec![1000, 1005]);
+        assert_eq!(state.take_cash_outs(), vec![("player3.near".parse().unwrap(), 1000), ("player1.near".parse().unwrap(), 995)]);
+        assert!(state.take_cash_outs().is_empty());
+
+        // the button skipped seat 1 on its way out, landing on what was seat 2
+        assert_eq!(state.table.dealer, 1);
+        assert_eq!(state.table.dealt_in, vec![true; 2]);
+    }
+
+    #[test]
+    fn slashing_several_players_keeps_everyone_elses_chips() {
+        let mut state = preflop(&[1000; 5]);
+        state.request_leave(0);
+        play(&mut state, &[(3, Call), (4, Call), (0, Call), (1, Call), (2, Check)]);
+        assert_eq!(state.table.phase, Phase::FLOP);
+        for player in [0, 2, 4] {
+            state.apply(player, Action::Reveal).unwrap();
         }
+        let stallers = state.stallers();
+        assert_eq!(stallers, vec![1, 3]);
+
+        // the leaver is cashed out with their share, and nobody else loses their seat
+        state.slash_players(&stallers).unwrap();
+        assert_eq!(state.player_account_ids, vec!["player2.near".parse().unwrap(), "player4.near".parse::<AccountId>().unwrap()]);
+        let cash_outs = state.take_cash_outs();
+        assert_eq!(cash_outs.iter().map(|(account_id, _)| account_id.clone()).collect::<Vec<_>>(), vec!["player0.near".parse::<AccountId>().unwrap()]);
+        assert_eq!(state.table.balances.iter().sum::<Balance>() + cash_outs.iter().map(|(_, chips)| chips).sum::<Balance>(), 5000);
+        assert_eq!(state.table.phase, Phase::SHUFFLE);
+        assert_eq!(state.table.dealt_in, vec![true; 2]);
     }
 
-    // reveal cards - each player has to call this (any order) with their reveal tokens calculated client side. number of cards revealed depends on the phase
-    pub fn reveal(&mut self, game_id: GameId, card_indices: Vec<usize>, reveal_tokens_with_proofs: Vec<BnRevealTokenWithProofBuf>) {
-        assert!(self.games.contains_key(&game_id), "game does not exist");
+    #[test]
+    fn slashed_players_are_not_reported_busted() {
+        let mut state = preflop(&[1000; 4]);
+        play(&mut state, &[(3, Call), (0, Call), (1, Call), (2, Check)]);
+        for player in [0, 2] {
+            state.apply(player, Action::Reveal).unwrap();
+        }
 
-        let mut game = self.games.get(&game_id).unwrap();
-        match game {
-            Game::InProgress(ref mut state) => {
-                let account_id = env::predecessor_account_id();
-                assert!(state.player_account_ids.contains(&account_id), "only players can reveal");
-                let player = state.player_account_ids.iter().position(|id| id == &account_id).unwrap();
-                assert!(!state.revealed_players[player], "you have already revealed");
-
-                let _indices_should_reveal: Vec<usize> = match state.phase {
-                    Phase::FLOP => {
-                        ((state.num_players() * 2)..(state.num_players() * 2 + 3)).collect()
-                    },
-                    Phase::TURN => {
-                        ((state.num_players() * 2 + 3)..(state.num_players() * 2 + 4)).collect()
-                    },
-                    Phase::RIVER => {
-                        ((state.num_players() * 2 + 4)..(state.num_players() * 2 + 5)).collect()
-                    },
-                    Phase::SHOWDOWN_REVEAL => {
-                       vec![player * 2, player * 2 + 1]
-                    }
-                    _ => panic!("cannot reveal cards in this phase")
-                };
+        // slashing leaves the stallers with no chips, but they're gone before anyone's checked for running out
+        state.slash_players(&state.stallers()).unwrap();
+        assert_eq!(state.player_account_ids, vec!["player0.near".parse().unwrap(), "player2.near".parse::<AccountId>().unwrap()]);
+        assert_eq!(state.table.balances, vec![2000, 2000]);
+        assert!(state.take_cash_outs().is_empty());
+        let logs = near_sdk::test_utils::get_logs();
+        assert_eq!(logs.iter().filter(|log| log.contains(r#""reason":"slashed""#)).count(), 2);
+        assert!(!logs.iter().any(|log| log.contains(r#""reason":"busted""#)));
+    }
 
-                // TODO check indices are sorted, unique, same len as reveal tokens, and match indices_should_reveal
-                let num_cards = match state.phase {
-                    Phase::FLOP => 3,
-                    Phase::TURN => 1,
-                    Phase::RIVER => 1,
-                    Phase::SHOWDOWN_REVEAL => 2,
-                    _ => unreachable!()
-                };
+    #[test]
+    fn late_seat_requests() {
+        let mut state = preflop(&[1000; 3]);
+        assert_eq!(state.request_seat(seat_request("player0.near", 500)), Err(ContractError::AlreadyJoined));
+        assert_eq!(state.request_seat(seat_request("late.near", 100)), Err(ContractError::BuyInOutOfRange { min: U128(200), max: U128(2000) }));
+        let seat_requests = || near_sdk::test_utils::get_logs().iter().filter(|log| log.contains(r#""event":"seat_requested""#)).count();
+        assert_eq!(seat_requests(), 0);
+        assert_eq!(state.request_seat(seat_request("late.near", 500)), Ok(()));
+        assert_eq!(seat_requests(), 1);
+        assert_eq!(state.request_seat(seat_request("late.near", 500)), Err(ContractError::AlreadyJoined));
+        assert_eq!(state.request_seat(seat_request("changed_mind.near", 300)), Ok(()));
+        assert_eq!(state.cancel_seat_request(&"changed_mind.near".parse().unwrap()), Some(300));
+        assert_eq!(state.num_players(), 3);
+
+        // seated once the hand is over
+        play(&mut state, &[(0, Fold), (1, Fold)]);
+        assert_eq!(state.num_players(), 4);
+        assert_eq!(state.player_account_id(3), "late.near".parse().unwrap());
+        assert_eq!(state.table.balances[3], 500);
+        assert_eq!(state.table.dealt_in, vec![true; 4]);
+        assert!(state.pending_seats.is_empty());
+
+        // and right away between hands
+        assert_eq!(state.request_seat(seat_request("later.near", 500)), Ok(()));
+        assert_eq!(state.num_players(), 5);
+
+        let mut state = between_hands(&[1000; MAX_PLAYERS]);
+        assert_eq!(state.request_seat(seat_request("late.near", 500)), Err(ContractError::TableFull));
+    }
 
-                assert!(card_indices.len() == num_cards, "wrong number of cards revealed");
-                assert!(reveal_tokens_with_proofs.len() == num_cards, "wrong number of reveal tokens revealed");
+    #[test]
+    fn top_up() {
+        let mut state = between_hands(&[1000, 50, 1000]);
 
-                let pp = self.trusted_setup_params.deserialize().expect("failed to deserialize trusted setup params");
-                let pk = state.player_game_pubkeys[player].deserialize().expect("failed to deserialize player pubkey");
+        // a short stack has to get back to the minimum buy-in, and nobody can go over the maximum
+        assert_eq!(state.top_up(1, 100), Err(ContractError::BuyInOutOfRange { min: U128(200), max: U128(2000) }));
+        assert_eq!(state.top_up(0, 1500), Err(ContractError::BuyInOutOfRange { min: U128(200), max: U128(2000) }));
+        assert_eq!(state.top_up(1, 450), Ok(()));
+        assert_eq!(state.top_up(0, 1000), Ok(()));
+        assert_eq!(state.table.balances, vec![2000, 500, 1000]);
 
-                for (card_idx, reveal_token_with_proof) in card_indices.into_iter().zip(reveal_tokens_with_proofs) {
-                    let masked_card = state.deck[card_idx].deserialize().expect("failed to deserialize masked card");
-                    let (reveal_token, proof) = reveal_token_with_proof.deserialize().expect("failed to deserialize reveal token with proof");
-                    BnCardProtocol::verify_reveal(&pp, &pk, &reveal_token, &masked_card, &proof).expect("failed to verify reveal token proof");
-                    state.set_reveal_token(card_idx, player, reveal_token_with_proof);
-                }
+        deal_hand(&mut state);
+        assert_eq!(state.top_up(2, 100), Err(ContractError::NotBetweenHands));
+    }
 
-                state.revealed_players[player] = true;
-
-                if state.all_players_revealed() {
-                    state.phase = match state.phase {
-                        Phase::FLOP => Phase::BET1,
-                        Phase::TURN => Phase::BET2,
-                        Phase::RIVER => Phase::BET3,
-                        Phase::SHOWDOWN_REVEAL => Phase::SHOWDOWN,
-                        _ => unreachable!()
-                    };
-
-                    if let Phase::SHOWDOWN = state.phase {
-                        state.do_showdown(&self.card_mapping, &pp);
-                        state.phase = Phase::SHUFFLE;
-                        state.dealer = (state.dealer + 1) % state.num_players();
-                        state.new_round();
-                    } else {
-                        state.turn = state.dealer;
-                        if state.player_is_folded(state.dealer) {
-                            state.turn = state.next_in_player().expect("next player should exist");
-                        }
-                        state.reset_revealed_players();
-                    }
-                }
-            },
-            _ => panic!("game is not in progress")
-        }
+    #[test]
+    fn keys_rotate_between_hands() {
+        let mut state = preflop(&[1000; 3]);
+        let new_key = BnPublicKeyBuf { buf: vec![1, 2, 3] };
+        assert_eq!(state.rotate_key(1, new_key.clone()), Err(ContractError::NotBetweenHands));
+
+        // the deck the hand was played with is left behind for the next shuffle, until someone's key changes
+        state.deck = vec![BnMaskedCardBuf { buf: vec![] }; DECK_SIZE];
+        play(&mut state, &[(0, Fold), (1, Fold)]);
+        assert_eq!(state.deck.len(), DECK_SIZE);
+
+        assert_eq!(state.rotate_key(1, new_key.clone()), Ok(()));
+        assert_eq!(state.player_game_pubkeys[1].buf, new_key.buf);
+        assert!(state.deck.is_empty());
+        assert_eq!(state.hand_keys().1[1].buf, new_key.buf);
     }
-}
 
-/*
- * The rest of this file holds the inline tests for the code above
- * Learn more about Rust tests: https://doc.rust-lang.org/book/ch11-01-writing-tests.html
- */
-#[cfg(test)]
-mod tests {
-    // TODO
-}
+    #[test]
+    fn busted_players_are_eliminated() {
+        let mut state = between_hands(&[1000, 0, 1000, 0]);
+        assert_eq!(state.player_account_ids, vec!["player0.near".parse().unwrap(), "player2.near".parse::<AccountId>().unwrap()]);
+        assert_eq!(state.table.balances, vec![1000, 1000]);
+        assert_eq!(state.table.dealt_in, vec![true; 2]);
+        assert_eq!(state.table.phase, Phase::SHUFFLE);
+        assert!(state.take_cash_outs().is_empty());
+    }
 
+    #[test]
+    fn game_finishes_when_one_player_has_all_the_chips() {
+        let mut state = between_hands(&[2000, 0, 0]);
+        assert!(state.finished());
+        assert_eq!(state.table.balances, vec![0]);
+        assert_eq!(state.take_cash_outs(), vec![("player0.near".parse().unwrap(), 2000)]);
+
+        // the last opponent leaving ends it too, once the hand is over
+        let mut state = preflop(&[1000, 1000]);
+        state.request_leave(1);
+        play(&mut state, &[(0, Fold)]);
+        assert!(state.finished());
+        assert_eq!(state.take_cash_outs(), vec![("player1.near".parse().unwrap(), 1005), ("player0.near".parse().unwrap(), 995)]);
+    }
+}
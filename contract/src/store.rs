@@ -0,0 +1,184 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::{
+    env,
+    AccountId,
+    IntoStorageKey,
+    collections::{LookupMap, UnorderedSet},
+};
+
+use crate::error::ContractError;
+use crate::events::GameEvent;
+use crate::{Game, GameId, GameState};
+
+/// Storage for all games, lobbies and in-progress alike.
+///
+/// `LookupMap::get` hands back an owned copy of the stored value, so mutating it does nothing
+/// unless it is written back. Every transition should therefore go through `modify` / `modify_state`,
+/// which load the game, apply the transition, and commit the result in one go. The result is only committed if
+/// the transition succeeds, and an error panics the receipt anyway, so a half-applied transition is never persisted.
+///
+/// `commit` and `remove` also keep the indexes used by the view methods (open lobbies, and the games each account is in) in sync.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct GameStore {
+    games: LookupMap<GameId, Game>,
+
+    /// ids of the games that are still waiting for players
+    open_lobbies: UnorderedSet<GameId>,
+
+    /// ids of the games each account is seated at, lobbies included
+    games_by_account: LookupMap<AccountId, Vec<GameId>>,
+}
+
+impl GameStore {
+    pub fn new<S: IntoStorageKey>(prefix: S) -> Self {
+        let prefix = prefix.into_storage_key();
+        Self {
+            games: LookupMap::new([&prefix[..], b"g"].concat()),
+            open_lobbies: UnorderedSet::new([&prefix[..], b"l"].concat()),
+            games_by_account: LookupMap::new([&prefix[..], b"a"].concat()),
+        }
+    }
+
+    pub fn contains(&self, game_id: &GameId) -> bool {
+        self.games.contains_key(game_id)
+    }
+
+    pub fn get(&self, game_id: &GameId) -> Option<Game> {
+        self.games.get(game_id)
+    }
+
+    pub fn load(&self, game_id: &GameId) -> Result<Game, ContractError> {
+        self.games.get(game_id).ok_or(ContractError::GameNotFound)
+    }
+
+    /// write a game back to storage, replacing whatever was there
+    pub fn commit(&mut self, game_id: &GameId, game: &Game) {
+        let prev = self.games.insert(game_id, game);
+        self.update_indexes(game_id, prev.as_ref(), Some(game));
+    }
+
+    pub fn remove(&mut self, game_id: &GameId) -> Option<Game> {
+        let prev = self.games.remove(game_id);
+        self.update_indexes(game_id, prev.as_ref(), None);
+        prev
+    }
+
+    /// the open lobbies, `limit` at a time starting from `from_index`
+    pub fn open_lobbies(&self, from_index: usize, limit: usize) -> Vec<GameId> {
+        self.open_lobbies.as_vector().iter().skip(from_index).take(limit).collect()
+    }
+
+    pub fn games_for_account(&self, account_id: &AccountId) -> Vec<GameId> {
+        self.games_by_account.get(account_id).unwrap_or_default()
+    }
+
+    fn update_indexes(&mut self, game_id: &GameId, prev: Option<&Game>, next: Option<&Game>) {
+        if let Some(Game::WaitingForPlayers(_)) = next {
+            self.open_lobbies.insert(game_id);
+        } else {
+            self.open_lobbies.remove(game_id);
+        }
+
+        let prev_players = prev.map(Game::player_account_ids).unwrap_or_default();
+        let next_players = next.map(Game::player_account_ids).unwrap_or_default();
+        for account_id in prev_players.iter().filter(|id| !next_players.contains(id)) {
+            let mut game_ids = self.games_for_account(account_id);
+            game_ids.retain(|id| id != game_id);
+            if game_ids.is_empty() {
+                self.games_by_account.remove(account_id);
+            } else {
+                self.games_by_account.insert(account_id, &game_ids);
+            }
+        }
+        for account_id in next_players.iter().filter(|id| !prev_players.contains(id)) {
+            let mut game_ids = self.games_for_account(account_id);
+            game_ids.push(*game_id);
+            self.games_by_account.insert(account_id, &game_ids);
+        }
+    }
+
+    /// load a game, apply `f` to it, and commit the result if `f` succeeded
+    pub fn modify<R, F>(&mut self, game_id: &GameId, f: F) -> Result<R, ContractError>
+    where
+        F: FnOnce(&mut Game) -> Result<R, ContractError>,
+    {
+        let mut game = self.load(game_id)?;
+        let res = f(&mut game)?;
+        self.commit(game_id, &game);
+        Ok(res)
+    }
+
+    /// same as `modify`, but for games that have already started. Bumps `last_modified` on every transition,
+    /// and logs a `phase_changed` event if the transition moved the game to a different phase.
+    /// A game the transition finished is removed instead of committed - `f` should hand back anything it still has to pay out.
+    pub fn modify_state<R, F>(&mut self, game_id: &GameId, f: F) -> Result<R, ContractError>
+    where
+        F: FnOnce(&mut GameState) -> Result<R, ContractError>,
+    {
+        let mut game = self.load(game_id)?;
+        let state = match game {
+            Game::InProgress(ref mut state) => state,
+            _ => return Err(ContractError::GameNotInProgress),
+        };
+
+        let phase = state.table.phase.clone();
+        let res = f(state)?;
+        state.last_modified = env::block_timestamp();
+        if state.table.phase != phase {
+            GameEvent::PhaseChanged {
+                game_id: state.id,
+                phase: state.table.phase.clone(),
+                turn: state.table.turn,
+                dealer: state.table.dealer,
+            }.emit();
+        }
+
+        if state.finished() {
+            self.remove(game_id);
+        } else {
+            self.commit(game_id, &game);
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use barnett_smart_card_protocol::discrete_log_cards::BnPublicKeyBuf;
+    use crate::config::TableConfig;
+    use crate::{GameLobby, Timeouts};
+
+    fn lobby(id: GameId, players: &[&str]) -> Game {
+        let account_ids = players.iter().map(|id| id.parse().unwrap()).collect::<Vec<AccountId>>();
+        let pubkeys = players.iter().map(|_| BnPublicKeyBuf { buf: vec![] }).collect();
+        let balances = players.iter().map(|_| 100).collect();
+        Game::WaitingForPlayers(GameLobby::new(id, account_ids, pubkeys, balances, Timeouts::default(), TableConfig::default()))
+    }
+
+    #[test]
+    fn indexes_follow_commits() {
+        let alice: AccountId = "alice.near".parse().unwrap();
+        let bob: AccountId = "bob.near".parse().unwrap();
+        let mut store = GameStore::new(b"s".to_vec());
+
+        store.commit(&[0; 4], &lobby([0; 4], &["alice.near"]));
+        store.commit(&[1; 4], &lobby([1; 4], &["bob.near"]));
+        assert_eq!(store.open_lobbies(0, 10), vec![[0; 4], [1; 4]]);
+        assert_eq!(store.open_lobbies(1, 10), vec![[1; 4]]);
+        assert_eq!(store.open_lobbies(0, 1), vec![[0; 4]]);
+
+        // bob joins alice's lobby
+        store.commit(&[0; 4], &lobby([0; 4], &["alice.near", "bob.near"]));
+        assert_eq!(store.games_for_account(&alice), vec![[0; 4]]);
+        assert_eq!(store.games_for_account(&bob), vec![[1; 4], [0; 4]]);
+
+        // and leaves again
+        store.commit(&[0; 4], &lobby([0; 4], &["alice.near"]));
+        assert_eq!(store.games_for_account(&bob), vec![[1; 4]]);
+
+        store.remove(&[1; 4]);
+        assert_eq!(store.open_lobbies(0, 10), vec![[0; 4]]);
+        assert!(store.games_for_account(&bob).is_empty());
+    }
+}
@@ -0,0 +1,145 @@
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::AccountId;
+use barnett_smart_card_protocol::discrete_log_cards::BnPublicKeyBuf;
+use rainbase_core::BetAmount;
+
+use crate::config::TableConfig;
+use crate::{GameId, GameLobby, GameState, Phase, Timeouts};
+
+/// What the view methods return for a lobby. Balances and timestamps are strings, since JSON numbers can't hold them.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LobbyView {
+    pub id: GameId,
+
+    /// the creator is always the first player
+    pub player_account_ids: Vec<AccountId>,
+    pub player_game_pubkeys: Vec<BnPublicKeyBuf>,
+
+    /// the chips each player bought in with
+    pub balances: Vec<U128>,
+    pub timeouts: Timeouts,
+    pub config: TableConfig,
+    pub created_at: U64,
+}
+
+impl From<&GameLobby> for LobbyView {
+    fn from(lobby: &GameLobby) -> Self {
+        Self {
+            id: lobby.id,
+            player_account_ids: lobby.player_account_ids.clone(),
+            player_game_pubkeys: lobby.player_game_pubkeys.clone(),
+            balances: lobby.balances.iter().map(|&b| U128(b)).collect(),
+            timeouts: lobby.timeouts.clone(),
+            config: lobby.config.clone(),
+            created_at: U64(lobby.created_at),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum BetView {
+    AllIn(U128),
+    In(U128),
+    Folded(U128),
+}
+
+impl From<&BetAmount> for BetView {
+    fn from(bet: &BetAmount) -> Self {
+        match bet {
+            BetAmount::AllIn(amount) => BetView::AllIn(U128(*amount)),
+            BetAmount::In(amount) => BetView::In(U128(*amount)),
+            BetAmount::Folded(amount) => BetView::Folded(U128(*amount)),
+        }
+    }
+}
+
+/// What the view methods return for a game in progress - everything but the deck and reveal tokens themselves,
+/// which clients fetch separately when they need to do the cryptography.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GameStateView {
+    pub id: GameId,
+    pub player_account_ids: Vec<AccountId>,
+    pub phase: Phase,
+    pub turn: usize,
+    pub dealer: usize,
+
+    /// what each player has put in this hand
+    pub bets: Vec<BetView>,
+
+    /// what each player has put in this betting round
+    pub street_bets: Vec<U128>,
+    pub current_bet: U128,
+    pub min_raise: U128,
+    pub num_bets: u8,
+
+    /// chips each player has behind, not counting what they've bet
+    pub balances: Vec<U128>,
+
+    /// players sitting out are skipped from the next hand on. Players leaving are also cashed out once the current hand is over
+    pub sitting_out: Vec<bool>,
+    pub leaving: Vec<bool>,
+
+    /// whether each player was dealt into the current hand
+    pub dealt_in: Vec<bool>,
+
+    /// late joiners waiting to be seated once the current hand is over
+    pub pending_seats: Vec<AccountId>,
+
+    pub checks: Vec<bool>,
+    pub revealed_players: Vec<bool>,
+
+    pub player_game_pubkeys: Vec<BnPublicKeyBuf>,
+    pub aggregate_pubkey: BnPublicKeyBuf,
+
+    /// 0 until the initial deck has been submitted or derived
+    pub deck_len: usize,
+
+    /// whether every masking proof for the initial deck has been checked
+    pub deck_verified: bool,
+
+    /// `reveal_tokens[card_index][player_index]` is true if that player has provided their reveal token for that card
+    pub reveal_tokens: Vec<Vec<bool>>,
+
+    pub timeouts: Timeouts,
+    pub config: TableConfig,
+    pub last_modified: U64,
+}
+
+impl From<&GameState> for GameStateView {
+    fn from(state: &GameState) -> Self {
+        Self {
+            id: state.id,
+            player_account_ids: state.player_account_ids.clone(),
+            phase: state.table.phase.clone(),
+            turn: state.table.turn,
+            dealer: state.table.dealer,
+            bets: state.table.bets.iter().map(BetView::from).collect(),
+            street_bets: state.table.street_bets.iter().map(|&b| U128(b)).collect(),
+            current_bet: U128(state.table.current_bet),
+            min_raise: U128(state.table.min_raise),
+            num_bets: state.table.num_bets,
+            balances: state.table.balances.iter().map(|&b| U128(b)).collect(),
+            sitting_out: state.sitting_out.clone(),
+            leaving: state.leaving.clone(),
+            dealt_in: state.table.dealt_in.clone(),
+            pending_seats: state.pending_seats.iter().map(|req| req.account_id.clone()).collect(),
+            checks: state.table.checks.clone(),
+            revealed_players: state.table.revealed_players.clone(),
+            player_game_pubkeys: state.player_game_pubkeys.clone(),
+            aggregate_pubkey: state.aggregate_pubkey.clone(),
+            deck_len: state.deck.len(),
+            deck_verified: state.deck_verified(),
+            reveal_tokens: state.reveal_tokens_with_proofs.iter()
+                .map(|tokens| tokens.iter().map(Option::is_some).collect())
+                .collect(),
+            timeouts: state.timeouts.clone(),
+            config: state.config.clone(),
+            last_modified: U64(state.last_modified),
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Chips;
+
+/// Something a player does at the table. Shuffling and revealing stand in for the cryptography - by the time one of them is
+/// applied, whoever's running the table has already checked the player's proofs.
+///
+/// Bet and raise amounts are what the player will have in for the current betting round, not an increment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// shuffle the deck, on your turn at the start of the hand
+    Shuffle,
+    /// publish your reveal tokens for the phase - everyone else's hole cards while dealing, then each street, then your own hand
+    Reveal,
+    Check,
+    Call,
+    /// open the betting when there's nothing to call
+    Bet(Chips),
+    Raise { to: Chips },
+    AllIn,
+    Fold,
+}
+
+impl Action {
+    /// the action as it's reported in bet events and errors. `None` for shuffling and revealing
+    pub fn kind(&self) -> Option<BetAction> {
+        match self {
+            Action::Shuffle | Action::Reveal => None,
+            Action::Check => Some(BetAction::Check),
+            Action::Call => Some(BetAction::Call),
+            Action::Bet(_) => Some(BetAction::Bet),
+            Action::Raise { .. } => Some(BetAction::Raise),
+            Action::AllIn => Some(BetAction::AllIn),
+            Action::Fold => Some(BetAction::Fold),
+        }
+    }
+}
+
+/// Every way chips go into the pot, plus folding.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BetAction {
+    Ante,
+    Blind,
+    Check,
+    Call,
+    Bet,
+    Raise,
+    AllIn,
+    Fold,
+}
@@ -0,0 +1,49 @@
+use std::fmt;
+
+use crate::{BetAction, Chips, Phase};
+
+/// returns `err` from the enclosing function unless `cond` holds
+macro_rules! ensure {
+    ($cond:expr, $err:expr) => {
+        if !$cond {
+            return Err($err);
+        }
+    };
+}
+pub(crate) use ensure;
+
+/// Why an action was rejected. The table is left exactly as it was.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// there's no seat with that number
+    NotAPlayer,
+    WrongPhase(Phase),
+    NotYourTurn,
+    /// the player already dealt or revealed this phase
+    AlreadyRevealed,
+    IllegalAction(BetAction),
+    RaiseTooSmall { minimum: Chips },
+    /// pot-limit and fixed-limit games cap how much you can raise to
+    RaiseTooBig { maximum: Chips },
+    InsufficientBalance { balance: Chips },
+    /// every seat stalled, so there's nobody to give the slashed chips to
+    NobodyToPay,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotAPlayer => write!(f, "not a player at this table"),
+            Error::WrongPhase(phase) => write!(f, "cannot do that in the {:?} phase", phase),
+            Error::NotYourTurn => write!(f, "it is not your turn"),
+            Error::AlreadyRevealed => write!(f, "you have already revealed this phase"),
+            Error::IllegalAction(action) => write!(f, "you cannot {:?} right now", action),
+            Error::RaiseTooSmall { minimum } => write!(f, "raise amount must be at least {}", minimum),
+            Error::RaiseTooBig { maximum } => write!(f, "raise amount can be at most {}", maximum),
+            Error::InsufficientBalance { balance } => write!(f, "not enough chips - you only have {}", balance),
+            Error::NobodyToPay => write!(f, "everyone at the table stalled, so there's nobody to pay"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
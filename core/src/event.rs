@@ -0,0 +1,19 @@
+use crate::{BetAction, Chips};
+
+/// What happened as a result of an action, in order. Phase changes aren't included - compare `phase` before and after instead.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// `player` anted, posted a blind, bet or folded. `amount` is their total stake in the pot afterwards
+    Bet {
+        player: usize,
+        action: BetAction,
+        amount: Chips,
+    },
+    /// `player` won `amount` chips, which are already back in their balance
+    PotWon {
+        player: usize,
+        amount: Chips,
+    },
+    /// the hand is settled. It's up to the caller to move the button, change seats around, and start the next one with `new_round`
+    HandOver,
+}
@@ -0,0 +1,35 @@
+//! The rules of a rainbase table - whose turn it is, what they're allowed to do, and where the chips go - with none of the
+//! cryptography or NEAR plumbing. The contract keeps a [`Table`] for every game and feeds it each player's move through [`apply`],
+//! once it's checked whatever proofs came with it. Everything here is deterministic and runs natively, so it can be tested
+//! (and fuzzed) without a blockchain.
+//!
+//! ```
+//! use rainbase_core::{apply, Action, BettingStructure, Phase, Stakes, Table};
+//!
+//! let stakes = Stakes { small_blind: 5, big_blind: 10, ante: None, betting: BettingStructure::NoLimit };
+//! let mut table = Table::new(vec![1000; 3], stakes);
+//! for player in 0..3 {
+//!     apply(&mut table, player, Action::Shuffle).unwrap();
+//! }
+//! for player in 0..3 {
+//!     apply(&mut table, player, Action::Reveal).unwrap();
+//! }
+//! assert_eq!(table.phase, Phase::BET0);
+//! assert_eq!(table.balances, vec![1000, 995, 990]);
+//! ```
+
+mod action;
+mod error;
+mod event;
+mod phase;
+pub mod pot;
+mod table;
+
+pub use action::{Action, BetAction};
+pub use error::Error;
+pub use event::Event;
+pub use phase::Phase;
+pub use table::{apply, BetAmount, BettingStructure, Stakes, Table, MAX_BETS_PER_ROUND};
+
+/// a number of chips. What a chip is worth is up to whoever's running the table
+pub type Chips = u128;
@@ -0,0 +1,33 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+
+/// Where a hand is up to. Each hand goes through these in order - the board is revealed a street at a time between the
+/// betting rounds - unless everyone but one player folds, in which case it ends in whichever betting round that happened.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[allow(non_camel_case_types)]
+pub enum Phase {
+    SHUFFLE,
+    DEAL,
+    BET0,
+    FLOP,
+    BET1,
+    TURN,
+    BET2,
+    RIVER,
+    BET3,
+    SHOWDOWN_REVEAL,
+    SHOWDOWN,
+    /// one player has won all the chips. They've been paid out, and the game is deleted along with this state
+    FINISHED,
+}
+
+impl Phase {
+    pub fn is_betting(&self) -> bool {
+        matches!(self, Phase::BET0 | Phase::BET1 | Phase::BET2 | Phase::BET3)
+    }
+
+    /// the phases where everyone dealt in has to publish reveal tokens before the hand can go on
+    pub fn is_revealing(&self) -> bool {
+        matches!(self, Phase::DEAL | Phase::FLOP | Phase::TURN | Phase::RIVER | Phase::SHOWDOWN_REVEAL)
+    }
+}
@@ -0,0 +1,212 @@
+use crate::Chips;
+
+/// A pot that can only be won by the `eligible` players.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pot {
+    pub amount: Chips,
+    pub eligible: Vec<usize>,
+}
+
+/// Splits what each player put in this hand into a main pot followed by side pots, in order.
+/// Every all-in amount caps a pot. Folded players' chips still go into the pots, but they can't win any of them.
+pub fn build_pots(contributions: &[Chips], folded: &[bool]) -> Vec<Pot> {
+    let mut levels = contributions.iter()
+        .zip(folded.iter())
+        .filter(|(&amount, &folded)| !folded && amount > 0)
+        .map(|(&amount, _)| amount)
+        .collect::<Vec<_>>();
+    levels.sort_unstable();
+    levels.dedup();
+
+    let mut pots = Vec::new();
+    let mut prev_level = 0;
+    for level in levels {
+        let amount = contributions.iter().map(|&c| c.min(level) - c.min(prev_level)).sum();
+        let eligible = (0..contributions.len()).filter(|&i| !folded[i] && contributions[i] >= level).collect();
+        pots.push(Pot { amount, eligible });
+        prev_level = level;
+    }
+
+    // a folded player can have put in more than any live player covered. Nobody can contest that, so it goes to the last pot
+    let leftover: Chips = contributions.iter().map(|&c| c.saturating_sub(prev_level)).sum();
+    if leftover > 0 {
+        match pots.last_mut() {
+            Some(pot) => pot.amount += leftover,
+            None => pots.push(Pot {
+                amount: leftover,
+                eligible: (0..contributions.len()).filter(|&i| !folded[i]).collect(),
+            }),
+        }
+    }
+
+    pots
+}
+
+/// Works out how much each player wins. `hand_ranks[i]` is `None` if player `i` has no hand (i.e. they folded), higher is better.
+///
+/// Each pot goes to the best hand among the players eligible for it. If several players tie for the best hand they split the pot
+/// evenly, and any odd chips left over are handed out one at a time going around the table from `first_seat` (the first seat left of the dealer).
+pub fn award_pots<T: PartialOrd>(pots: &[Pot], hand_ranks: &[Option<T>], first_seat: usize) -> Vec<Chips> {
+    let num_seats = hand_ranks.len();
+    let mut winnings = vec![0; num_seats];
+    for pot in pots {
+        let best = pot.eligible.iter()
+            .filter_map(|&i| hand_ranks[i].as_ref())
+            .reduce(|best, rank| if rank > best { rank } else { best })
+            .expect("pot has no eligible players");
+
+        let mut winners = pot.eligible.iter()
+            .copied()
+            .filter(|&i| hand_ranks[i].as_ref() == Some(best))
+            .collect::<Vec<_>>();
+        winners.sort_by_key(|&i| (i + num_seats - first_seat) % num_seats);
+
+        let share = pot.amount / winners.len() as Chips;
+        let odd_chips = (pot.amount % winners.len() as Chips) as usize;
+        for (k, &winner) in winners.iter().enumerate() {
+            winnings[winner] += if k < odd_chips { share + 1 } else { share };
+        }
+    }
+    winnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use poker::{Card, Evaluator, Rank, Suit};
+
+    #[test]
+    fn single_pot_when_nobody_is_all_in() {
+        let pots = build_pots(&[20, 20, 5, 20], &[false, false, true, false]);
+        assert_eq!(pots, vec![Pot { amount: 65, eligible: vec![0, 1, 3] }]);
+    }
+
+    #[test]
+    fn three_way_all_in() {
+        // A is all-in for 50, B is all-in for 100, C covers both
+        let contributions = [50, 100, 100];
+        let folded = [false, false, false];
+        let pots = build_pots(&contributions, &folded);
+        assert_eq!(pots, vec![
+            Pot { amount: 150, eligible: vec![0, 1, 2] },
+            Pot { amount: 100, eligible: vec![1, 2] },
+        ]);
+
+        // the short stack wins, but only takes what they covered. C beats B for the side pot
+        assert_eq!(award_pots(&pots, &[Some(3), Some(1), Some(2)], 0), vec![150, 0, 100]);
+
+        // B wins everything they contested
+        assert_eq!(award_pots(&pots, &[Some(2), Some(3), Some(1)], 0), vec![0, 250, 0]);
+    }
+
+    #[test]
+    fn four_way_all_in() {
+        // stacks of 25, 50 and 75 all-in, the big stack calls 75
+        let contributions = [25, 50, 75, 75];
+        let folded = [false; 4];
+        let pots = build_pots(&contributions, &folded);
+        assert_eq!(pots, vec![
+            Pot { amount: 100, eligible: vec![0, 1, 2, 3] },
+            Pot { amount: 75, eligible: vec![1, 2, 3] },
+            Pot { amount: 50, eligible: vec![2, 3] },
+        ]);
+
+        assert_eq!(award_pots(&pots, &[Some(4), Some(3), Some(1), Some(2)], 0), vec![100, 75, 0, 50]);
+        assert_eq!(award_pots(&pots, &[Some(1), Some(2), Some(3), Some(4)], 0), vec![0, 0, 0, 225]);
+    }
+
+    #[test]
+    fn four_way_all_in_with_a_fold() {
+        // the third player put in 40 and then folded
+        let contributions = [25, 50, 40, 50];
+        let folded = [false, false, true, false];
+        let pots = build_pots(&contributions, &folded);
+        assert_eq!(pots, vec![
+            Pot { amount: 100, eligible: vec![0, 1, 3] },
+            Pot { amount: 65, eligible: vec![1, 3] },
+        ]);
+
+        // the folded player's hand doesn't matter
+        assert_eq!(award_pots(&pots, &[Some(3), Some(1), None, Some(2)], 0), vec![100, 0, 0, 65]);
+    }
+
+    #[test]
+    fn folded_chips_beyond_every_live_player_go_to_last_pot() {
+        let contributions = [10, 30, 20];
+        let folded = [false, true, false];
+        let pots = build_pots(&contributions, &folded);
+        assert_eq!(pots, vec![
+            Pot { amount: 30, eligible: vec![0, 2] },
+            Pot { amount: 30, eligible: vec![2] },
+        ]);
+        assert_eq!(pots.iter().map(|pot| pot.amount).sum::<Chips>(), 60);
+    }
+
+    #[test]
+    fn last_player_standing_takes_everything() {
+        let contributions = [10, 30, 20];
+        let folded = [true, false, true];
+        let pots = build_pots(&contributions, &folded);
+        assert_eq!(award_pots(&pots, &[None, Some(()), None], 0), vec![0, 60, 0]);
+    }
+
+    #[test]
+    fn tied_hands_split_the_pot() {
+        let pots = build_pots(&[30, 30, 30], &[false; 3]);
+        assert_eq!(award_pots(&pots, &[Some(2), Some(1), Some(2)], 1), vec![45, 0, 45]);
+        assert_eq!(award_pots(&pots, &[Some(2), Some(2), Some(2)], 1), vec![30, 30, 30]);
+    }
+
+    #[test]
+    fn odd_chips_go_to_first_seats_left_of_dealer() {
+        // 25 doesn't split two ways. The dealer is seat 0, so seat 1 is first to the left
+        let pots = build_pots(&[5, 10, 10], &[true, false, false]);
+        assert_eq!(award_pots(&pots, &[None, Some(1), Some(1)], 1), vec![0, 13, 12]);
+
+        // the dealer is seat 1, so it goes around to seat 2 first
+        assert_eq!(award_pots(&pots, &[None, Some(1), Some(1)], 2), vec![0, 12, 13]);
+
+        // 100 split three ways leaves one odd chip. If the dealer is seat 3, seat 0 is first to the left
+        let pots = build_pots(&[25, 25, 25, 25], &[false; 4]);
+        assert_eq!(award_pots(&pots, &[Some(1), Some(0), Some(1), Some(1)], 0), vec![34, 0, 33, 33]);
+
+        // if the dealer is seat 0, seat 1 is first to the left but lost, so the chip goes to seat 2
+        assert_eq!(award_pots(&pots, &[Some(1), Some(0), Some(1), Some(1)], 1), vec![33, 0, 34, 33]);
+    }
+
+    #[test]
+    fn split_main_pot_with_unsplit_side_pot() {
+        // the short stack ties with the big stack for the main pot, but only the big stack can win the side pot
+        let contributions = [20, 50, 50];
+        let pots = build_pots(&contributions, &[false; 3]);
+        assert_eq!(award_pots(&pots, &[Some(5), Some(1), Some(5)], 1), vec![30, 0, 90]);
+
+        // three-way tie in the main pot, two-way tie in the side pot
+        assert_eq!(award_pots(&pots, &[Some(5), Some(5), Some(5)], 1), vec![20, 50, 50]);
+    }
+
+    #[test]
+    fn board_straight_is_a_tie() {
+        let board = [
+            Card::new(Rank::Five, Suit::Spades),
+            Card::new(Rank::Six, Suit::Hearts),
+            Card::new(Rank::Seven, Suit::Diamonds),
+            Card::new(Rank::Eight, Suit::Clubs),
+            Card::new(Rank::Nine, Suit::Spades),
+        ];
+        let holes = [
+            [Card::new(Rank::Two, Suit::Clubs), Card::new(Rank::Three, Suit::Diamonds)],
+            [Card::new(Rank::Two, Suit::Hearts), Card::new(Rank::Three, Suit::Spades)],
+            [Card::new(Rank::Four, Suit::Hearts), Card::new(Rank::Two, Suit::Diamonds)],
+        ];
+
+        let evaluator = Evaluator::new();
+        let hand_ranks = holes.iter()
+            .map(|hole| Some(evaluator.evaluate([&hole[..], &board[..]].concat()).unwrap()))
+            .collect::<Vec<_>>();
+
+        // the first two play the board, the third doesn't improve on it either
+        let pots = build_pots(&[10, 10, 10], &[false; 3]);
+        assert_eq!(award_pots(&pots, &hand_ranks, 1), vec![10, 10, 10]);
+    }
+}
@@ -0,0 +1,1170 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+
+use crate::error::ensure;
+use crate::pot::{self, Pot};
+use crate::{Action, BetAction, Chips, Error, Event, Phase};
+
+/// in fixed-limit games, betting is capped at a bet and three raises per round
+pub const MAX_BETS_PER_ROUND: u8 = 4;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[allow(clippy::enum_variant_names)]
+pub enum BettingStructure {
+    /// bets and raises can be anything up to the player's whole stack
+    NoLimit,
+    /// raises are capped at the size of the pot after calling
+    PotLimit,
+    /// bets and raises are exactly one big blind, or two on the turn and river, with at most four bets a round
+    FixedLimit,
+}
+
+/// What the table plays for, in chips.
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stakes {
+    pub small_blind: Chips,
+    pub big_blind: Chips,
+
+    /// paid by every player at the start of each hand, on top of the blinds. It goes in the pot but doesn't count towards calling
+    pub ante: Option<Chips>,
+    pub betting: BettingStructure,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq, Eq)]
+pub enum BetAmount {
+    /// the player is all-in, having bet everything they had
+    AllIn(Chips),
+
+    /// the player bet some amount, isn't all-in, and hasn't folded
+    In(Chips),
+
+    /// the player folded and is out of the round with their bet still on the table
+    Folded(Chips),
+}
+
+impl BetAmount {
+    pub fn amount(&self) -> Chips {
+        match self {
+            BetAmount::AllIn(amount) | BetAmount::In(amount) | BetAmount::Folded(amount) => *amount,
+        }
+    }
+}
+
+/// Everything about a table that the rules care about. Seats are numbered in turn order, going around the table.
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq, Eq)]
+pub struct Table {
+    /// current phase of the game
+    pub phase: Phase,
+
+    /// the current player whose turn it is
+    pub turn: usize,
+
+    /// the current "dealer". Since everyone shuffles, in practice this is just the player who publishes the initial deck for the round
+    /// this is also used to determine who the little blind, big blind, and action is
+    /// this increases by one each round
+    pub dealer: usize,
+
+    /// the players playing the current hand. Everyone else is treated as if they'd already folded, and doesn't shuffle or reveal anything
+    pub dealt_in: Vec<bool>,
+
+    /// used to check which players have revealed.
+    pub revealed_players: Vec<bool>,
+
+    /// the amounts each player has bet so far this hand
+    pub bets: Vec<BetAmount>,
+
+    /// the amounts each player has bet in the current betting round
+    pub street_bets: Vec<Chips>,
+
+    /// the bet everyone has to match to stay in the current betting round
+    pub current_bet: Chips,
+
+    /// the size of the last full raise this round - the next raise has to be at least this big
+    pub min_raise: Chips,
+
+    /// the number of bets and full raises this round, counting the big blind. Only fixed-limit games cap this
+    pub num_bets: u8,
+
+    /// which players have acted since the betting was last reopened by a full raise
+    pub checks: Vec<bool>,
+
+    /// the number of "chips" each player has behind - chips they've bet are already out of here
+    pub balances: Vec<Chips>,
+
+    pub stakes: Stakes,
+}
+
+/// `actor` takes `action`, if they're allowed to. Returns what happened, in order - on an error, `table` is left untouched.
+///
+/// Shuffling goes around the table from the dealer. Once everyone's dealt, the blinds go in and the first betting round starts,
+/// and once the betting's done everyone reveals the next street. After the last one, everyone still in reveals their hand and
+/// the table waits in `SHOWDOWN` for the caller to rank the hands and call [`Table::showdown`].
+pub fn apply(table: &mut Table, actor: usize, action: Action) -> Result<Vec<Event>, Error> {
+    table.check(actor, action)?;
+
+    let mut events = Vec::new();
+    match action {
+        Action::Shuffle => table.shuffled(),
+        Action::Reveal => table.revealed(actor, &mut events),
+        _ => table.bet(actor, action, &mut events),
+    }
+    Ok(events)
+}
+
+impl Table {
+    /// a table with everyone dealt in and the button on seat 0, waiting for the first hand to be shuffled
+    pub fn new(balances: Vec<Chips>, stakes: Stakes) -> Self {
+        let num_players = balances.len();
+        Self {
+            phase: Phase::SHUFFLE,
+            turn: 0,
+            dealer: 0,
+            dealt_in: vec![true; num_players],
+            revealed_players: vec![false; num_players],
+            bets: vec![BetAmount::In(0); num_players],
+            street_bets: vec![0; num_players],
+            current_bet: 0,
+            min_raise: stakes.big_blind,
+            num_bets: 0,
+            checks: vec![false; num_players],
+            balances,
+            stakes,
+        }
+    }
+
+    pub fn num_players(&self) -> usize {
+        self.balances.len()
+    }
+
+    pub fn num_dealt_in(&self) -> usize {
+        self.dealt_in.iter().filter(|&&x| x).count()
+    }
+
+    /// the next seat after `seat` that's dealt into the current hand, going around the table
+    pub fn next_dealt_in(&self, seat: usize) -> usize {
+        (1..=self.num_players()).map(|k| (seat + k) % self.num_players()).find(|&i| self.dealt_in[i]).unwrap_or(seat)
+    }
+
+    /// true if nobody has shuffled yet this hand, so it's safe to change who's sitting at the table
+    pub fn between_hands(&self) -> bool {
+        matches!(self.phase, Phase::SHUFFLE) && self.turn == self.dealer
+    }
+
+    pub fn all_players_revealed(&self) -> bool {
+        self.revealed_players.iter().all(|&x| x)
+    }
+
+    /// takes `seat` away, keeping the button where it is. Returns the chips they had behind.
+    /// the rest of the hand's state is left alone, so this should be followed by `new_round`
+    pub fn remove_seat(&mut self, seat: usize) -> Chips {
+        self.dealt_in.remove(seat);
+        let chips = self.balances.remove(seat);
+
+        if seat < self.dealer {
+            self.dealer -= 1;
+        }
+        if self.num_players() > 0 {
+            self.dealer %= self.num_players();
+        }
+        chips
+    }
+
+    /// seats a new player with `chips`. They're not dealt in until the caller says so
+    pub fn add_seat(&mut self, chips: Chips) {
+        self.balances.push(chips);
+        self.dealt_in.push(false);
+    }
+
+    /// clears out the last hand. The dealer shuffles first
+    pub fn new_round(&mut self) {
+        self.reset_bets();
+        self.reset_checks();
+        self.reset_revealed_players();
+        self.current_bet = 0;
+        self.min_raise = self.stakes.big_blind;
+        self.num_bets = 0;
+        self.turn = self.dealer;
+    }
+
+    /// whether `actor` can take `action` right now, without taking it. `apply` does this first, but it's cheap enough
+    /// to do before checking any proofs that came with the action, too
+    pub fn check(&self, actor: usize, action: Action) -> Result<(), Error> {
+        ensure!(actor < self.num_players(), Error::NotAPlayer);
+        match action {
+            Action::Shuffle => {
+                ensure!(matches!(self.phase, Phase::SHUFFLE), Error::WrongPhase(self.phase.clone()));
+                ensure!(self.turn == actor, Error::NotYourTurn);
+            },
+            Action::Reveal => {
+                ensure!(self.phase.is_revealing(), Error::WrongPhase(self.phase.clone()));
+                ensure!(!self.revealed_players[actor], Error::AlreadyRevealed);
+            },
+            _ => {
+                ensure!(self.phase.is_betting(), Error::WrongPhase(self.phase.clone()));
+                ensure!(self.turn == actor, Error::NotYourTurn);
+                self.check_bet(actor, action)?;
+            },
+        }
+        Ok(())
+    }
+
+    /// settles the hand once everyone still in has revealed it - each pot goes to the best hand eligible for it.
+    /// `hand_ranks[i]` is `None` for players with no hand (they folded or weren't dealt in), higher is better
+    pub fn showdown<T: PartialOrd>(&mut self, hand_ranks: &[Option<T>]) -> Vec<Event> {
+        assert_eq!(self.phase, Phase::SHOWDOWN, "hands can only be ranked once they've been revealed");
+        let mut events = Vec::new();
+        self.award_pots(hand_ranks, &mut events);
+        events.push(Event::HandOver);
+        events
+    }
+
+    /// the hand can't go on without the stallers' reveal tokens, so it's called off - nobody pays into the pot, and the stallers'
+    /// chips are split evenly between everyone else in the hand, or between the players sitting it out if everyone in it stalled.
+    /// The stallers are left with nothing, so the caller has to remove them all before it does anything that treats an empty stack
+    /// as a busted player. Fails if every seat stalled, since there's nobody to pay
+    pub fn slash(&mut self, stallers: &[usize]) -> Result<Vec<Event>, Error> {
+        ensure!(stallers.iter().all(|&i| i < self.num_players()), Error::NotAPlayer);
+        let anyone_in_hand = (0..self.num_players()).any(|i| self.dealt_in[i] && !stallers.contains(&i));
+        let receives = |i: usize| !stallers.contains(&i) && (self.dealt_in[i] || !anyone_in_hand);
+        let recipients = (0..self.num_players()).filter(|&i| receives(i)).collect::<Vec<_>>();
+        ensure!(!recipients.is_empty(), Error::NobodyToPay);
+        let hand_ranks = (0..self.num_players()).map(|i| receives(i).then_some(())).collect::<Vec<_>>();
+
+        self.return_contributions();
+        let slashed = stallers.iter().map(|&i| std::mem::take(&mut self.balances[i])).sum();
+        let first_seat = (self.dealer + 1) % self.num_players();
+        let winnings = pot::award_pots(&[Pot { amount: slashed, eligible: recipients }], &hand_ranks, first_seat);
+
+        let mut events = Vec::new();
+        self.pay_out(&winnings, &mut events);
+        Ok(events)
+    }
+
+    /// heads-up, the dealer posts the small blind so that they act first before the flop and last after it
+    pub fn small_blind_seat(&self) -> usize {
+        if self.num_dealt_in() == 2 {
+            self.dealer
+        } else {
+            self.next_dealt_in(self.dealer)
+        }
+    }
+
+    pub fn big_blind_seat(&self) -> usize {
+        self.next_dealt_in(self.small_blind_seat())
+    }
+
+    pub fn player_is_folded(&self, i: usize) -> bool {
+        matches!(self.bets[i], BetAmount::Folded(_))
+    }
+
+    /// the number of chips each player has put in this hand
+    pub fn contributions(&self) -> Vec<Chips> {
+        self.bets.iter().map(BetAmount::amount).collect()
+    }
+
+    /// the main pot followed by any side pots
+    pub fn pots(&self) -> Vec<Pot> {
+        let folded = (0..self.num_players()).map(|i| self.player_is_folded(i)).collect::<Vec<_>>();
+        pot::build_pots(&self.contributions(), &folded)
+    }
+
+    /// the smallest total a player can bet or raise to this round, unless they're going all-in for less
+    pub fn min_raise_to(&self) -> Chips {
+        self.current_bet + self.min_raise
+    }
+
+    /// the largest total `player` can bet or raise to this round, not counting what they can afford.
+    /// pot-limit raises are capped at the size of the pot after calling, fixed-limit raises are always the minimum
+    pub fn max_raise_to(&self, player: usize) -> Chips {
+        match self.stakes.betting {
+            BettingStructure::NoLimit => Chips::MAX,
+            BettingStructure::PotLimit => {
+                let pot: Chips = self.contributions().iter().sum();
+                self.current_bet + pot + self.amount_to_call(player)
+            },
+            BettingStructure::FixedLimit => self.min_raise_to(),
+        }
+    }
+
+    /// how much more `player` has to put in to stay in the hand
+    pub fn amount_to_call(&self, player: usize) -> Chips {
+        self.current_bet.saturating_sub(self.street_bets[player])
+    }
+
+    /// `actor` shuffled - it's the next player's turn, or time to deal once it's back around to the dealer
+    fn shuffled(&mut self) {
+        self.turn = self.next_dealt_in(self.turn);
+        if self.turn == self.dealer {
+            self.phase = Phase::DEAL;
+        }
+    }
+
+    /// `actor` revealed what the phase needed from them. Once everyone has, the hand moves on
+    fn revealed(&mut self, actor: usize, events: &mut Vec<Event>) {
+        self.revealed_players[actor] = true;
+        if !self.all_players_revealed() {
+            return;
+        }
+
+        match self.phase {
+            Phase::DEAL => self.post_blinds(events),
+            Phase::SHOWDOWN_REVEAL => self.phase = Phase::SHOWDOWN,
+            _ => self.start_next_street(),
+        }
+    }
+
+    /// `player` takes `action` on their turn, and the betting moves along
+    fn bet(&mut self, player: usize, action: Action, events: &mut Vec<Event>) {
+        match action {
+            Action::Check => {},
+            Action::Call => self.commit_chips(player, self.amount_to_call(player)),
+            Action::Bet(amount) | Action::Raise { to: amount } => self.bet_to(player, amount),
+            Action::AllIn => self.bet_to(player, self.street_bets[player] + self.balances[player]),
+            Action::Fold => self.set_folded_player(player),
+            Action::Shuffle | Action::Reveal => unreachable!(),
+        }
+        self.checks[player] = true;
+        self.emit_bet(player, action.kind().unwrap(), events);
+        self.finish_action(events);
+    }
+
+    /// whether `action` is one `player` is allowed to take, and for an amount they're allowed to bet
+    fn check_bet(&self, player: usize, action: Action) -> Result<(), Error> {
+        let illegal = Error::IllegalAction(action.kind().expect("not a betting action"));
+        match action {
+            Action::Check => ensure!(self.player_can_check(), illegal),
+            Action::Call => ensure!(self.player_can_call(), illegal),
+            Action::Bet(amount) => {
+                ensure!(self.player_can_bet(), illegal);
+                self.check_raise_to(player, amount)?;
+            },
+            Action::Raise { to: amount } => {
+                ensure!(self.player_can_raise(), illegal);
+                self.check_raise_to(player, amount)?;
+            },
+            Action::AllIn => ensure!(self.player_can_all_in(), illegal),
+            Action::Fold => ensure!(self.player_can_fold(), illegal),
+            Action::Shuffle | Action::Reveal => unreachable!(),
+        }
+        Ok(())
+    }
+
+    fn check_raise_to(&self, player: usize, amount: Chips) -> Result<(), Error> {
+        let stack = self.street_bets[player] + self.balances[player];
+        ensure!(amount <= stack, Error::InsufficientBalance { balance: self.balances[player] });
+        ensure!(amount <= self.max_raise_to(player), Error::RaiseTooBig { maximum: self.max_raise_to(player) });
+        ensure!(
+            amount >= self.min_raise_to() || (amount == stack && amount > self.current_bet),
+            Error::RaiseTooSmall { minimum: self.min_raise_to() }
+        );
+        Ok(())
+    }
+
+    fn reset_checks(&mut self) {
+        self.checks = vec![false; self.num_players()];
+    }
+
+    /// players who aren't dealt in have nothing to reveal, so they start out marked as done
+    fn reset_revealed_players(&mut self) {
+        self.revealed_players = self.dealt_in.iter().map(|&x| !x).collect();
+    }
+
+    /// players who aren't dealt in are treated as having folded before the hand started
+    fn reset_bets(&mut self) {
+        self.bets = self.dealt_in.iter().map(|&x| if x { BetAmount::In(0) } else { BetAmount::Folded(0) }).collect();
+        self.street_bets = vec![0; self.num_players()];
+    }
+
+    fn num_players_in(&self) -> usize {
+        (0..self.num_players()).filter(|&i| !self.player_is_folded(i)).count()
+    }
+
+    /// true if the player still has decisions to make this hand - they haven't folded and aren't all-in
+    fn player_can_act(&self, i: usize) -> bool {
+        matches!(self.bets[i], BetAmount::In(_))
+    }
+
+    /// the next player after `seat` who can still act, going around the table
+    fn next_to_act(&self, seat: usize) -> Option<usize> {
+        (1..=self.num_players()).map(|k| (seat + k) % self.num_players()).find(|&i| self.player_can_act(i))
+    }
+
+    /// moves up to `amount` of `player`'s chips into the pot, returning how much went in. If that's everything they have left, they're all-in
+    fn take_chips(&mut self, player: usize, amount: Chips) -> Chips {
+        let amount = amount.min(self.balances[player]);
+        self.balances[player] -= amount;
+
+        let total = self.bets[player].amount() + amount;
+        self.bets[player] = if self.balances[player] == 0 { BetAmount::AllIn(total) } else { BetAmount::In(total) };
+        amount
+    }
+
+    /// like `take_chips`, but the chips count towards what `player` has in for the current betting round
+    fn commit_chips(&mut self, player: usize, amount: Chips) {
+        self.street_bets[player] += self.take_chips(player, amount);
+    }
+
+    fn set_folded_player(&mut self, player_idx: usize) {
+        self.bets[player_idx] = match self.bets[player_idx] {
+            BetAmount::In(amt) | BetAmount::AllIn(amt) => BetAmount::Folded(amt),
+            BetAmount::Folded(_) => panic!("player is already folded")
+        };
+    }
+
+    /// everyone pays the table's ante, if it has one. Antes are dead money - they go in the pot, but nobody has to match them
+    fn post_antes(&mut self, events: &mut Vec<Event>) {
+        if let Some(ante) = self.stakes.ante {
+            // players who aren't dealt in don't pay it
+            let players = (0..self.num_players()).filter(|&i| self.dealt_in[i]).collect::<Vec<_>>();
+            for player in players {
+                self.take_chips(player, ante);
+                self.emit_bet(player, BetAction::Ante, events);
+            }
+        }
+    }
+
+    /// everyone has their cards, so the antes and blinds go in and the first betting round starts left of the big blind.
+    /// nobody has to send a transaction for this - short stacks just go all-in for whatever they have
+    fn post_blinds(&mut self, events: &mut Vec<Event>) {
+        self.post_antes(events);
+        for (seat, amount) in [(self.small_blind_seat(), self.stakes.small_blind), (self.big_blind_seat(), self.stakes.big_blind)] {
+            self.commit_chips(seat, amount);
+            self.emit_bet(seat, BetAction::Blind, events);
+        }
+
+        // the big blind sets the price to call, even if they couldn't cover it
+        self.phase = Phase::BET0;
+        self.current_bet = self.stakes.big_blind;
+        self.min_raise = self.bet_size();
+        self.num_bets = 1;
+        self.open_betting(self.big_blind_seat());
+    }
+
+    /// moves the betting along after a player acts - either someone won because everyone else folded,
+    /// the betting round is over, or it's the next player's turn
+    fn finish_action(&mut self, events: &mut Vec<Event>) {
+        if self.num_players_in() == 1 {
+            // that player won
+            let winner = (0..self.num_players()).find(|&i| !self.player_is_folded(i)).unwrap();
+            self.transfer_pot(winner, events);
+            events.push(Event::HandOver);
+        } else if self.betting_round_over() {
+            self.close_betting();
+        } else {
+            self.turn = self.next_to_act(self.turn).expect("someone should still be able to act");
+        }
+    }
+
+    /// the round is over once everyone who can still act has matched the current bet and has acted since the last full raise.
+    /// if only one player can still act, there's nobody left to bet against once they've matched
+    fn betting_round_over(&self) -> bool {
+        let active = (0..self.num_players()).filter(|&i| self.player_can_act(i)).collect::<Vec<_>>();
+        active.iter().all(|&i| self.street_bets[i] == self.current_bet)
+            && (active.len() <= 1 || active.iter().all(|&i| self.checks[i]))
+    }
+
+    /// starts a betting round with the first player after `seat` who can act, or skips it if nobody's left to bet against
+    fn open_betting(&mut self, seat: usize) {
+        match self.next_to_act(seat) {
+            Some(next) if !self.betting_round_over() => self.turn = next,
+            _ => self.close_betting(),
+        }
+    }
+
+    /// the betting round is over - on to revealing the next street, or everyone's hands
+    fn close_betting(&mut self) {
+        self.phase = match self.phase {
+            Phase::BET0 => Phase::FLOP,
+            Phase::BET1 => Phase::TURN,
+            Phase::BET2 => Phase::RIVER,
+            Phase::BET3 => Phase::SHOWDOWN_REVEAL,
+            _ => unreachable!()
+        };
+        self.reset_revealed_players();
+
+        if let Phase::SHOWDOWN_REVEAL = self.phase {
+            // players who folded don't show their cards
+            for i in 0..self.num_players() {
+                self.revealed_players[i] = self.player_is_folded(i);
+            }
+        }
+    }
+
+    /// everyone has revealed the flop, turn or river - bets for the new round start from zero, and the first player left of the dealer acts first
+    fn start_next_street(&mut self) {
+        self.phase = match self.phase {
+            Phase::FLOP => Phase::BET1,
+            Phase::TURN => Phase::BET2,
+            Phase::RIVER => Phase::BET3,
+            _ => unreachable!()
+        };
+        self.street_bets = vec![0; self.num_players()];
+        self.current_bet = 0;
+        self.min_raise = self.bet_size();
+        self.num_bets = 0;
+        self.reset_checks();
+        self.open_betting(self.dealer);
+    }
+
+    /// the smallest bet or raise this round. In fixed-limit games it's also the only size allowed -
+    /// the big blind before the flop and on the flop, and twice that on the turn and river
+    fn bet_size(&self) -> Chips {
+        match (self.stakes.betting, &self.phase) {
+            (BettingStructure::FixedLimit, Phase::BET2 | Phase::BET3) => 2 * self.stakes.big_blind,
+            _ => self.stakes.big_blind,
+        }
+    }
+
+    /// commits chips until `player` has `amount` in this round. If that's more than the current bet it's a raise -
+    /// a full raise reopens the betting for everyone else, a short all-in only makes them call the difference
+    fn bet_to(&mut self, player: usize, amount: Chips) {
+        self.commit_chips(player, amount - self.street_bets[player]);
+        if amount > self.current_bet {
+            let raise = amount - self.current_bet;
+            if raise >= self.min_raise {
+                self.min_raise = raise;
+                self.num_bets += 1;
+                self.reset_checks();
+            }
+            self.current_bet = amount;
+        }
+    }
+
+    fn player_can_check(&self) -> bool {
+        self.player_can_act(self.turn) && self.amount_to_call(self.turn) == 0
+    }
+
+    fn player_can_call(&self) -> bool {
+        self.player_can_act(self.turn) && self.amount_to_call(self.turn) > 0
+    }
+
+    fn player_can_bet(&self) -> bool {
+        self.player_can_act(self.turn) && self.current_bet == 0
+    }
+
+    /// a player who has already acted this round can't raise again unless someone else has made a full raise since
+    fn player_can_raise(&self) -> bool {
+        self.player_can_act(self.turn)
+            && self.current_bet > 0
+            && !self.checks[self.turn]
+            && !self.betting_capped()
+            && self.balances[self.turn] > self.amount_to_call(self.turn)
+    }
+
+    fn betting_capped(&self) -> bool {
+        self.stakes.betting == BettingStructure::FixedLimit && self.num_bets >= MAX_BETS_PER_ROUND
+    }
+
+    /// going all-in is always allowed as a call. It's only allowed as a bet or raise if the player could bet or raise that much
+    fn player_can_all_in(&self) -> bool {
+        let stack = self.street_bets[self.turn] + self.balances[self.turn];
+        self.player_can_act(self.turn)
+            && (self.balances[self.turn] <= self.amount_to_call(self.turn)
+                || ((self.player_can_bet() || self.player_can_raise()) && stack <= self.max_raise_to(self.turn)))
+    }
+
+    fn player_can_fold(&self) -> bool {
+        self.player_can_act(self.turn)
+    }
+
+    /// calls the hand off, giving everyone back what they put in
+    fn return_contributions(&mut self) {
+        for (player, contribution) in self.contributions().into_iter().enumerate() {
+            self.balances[player] += contribution;
+        }
+        self.reset_bets();
+    }
+
+    /// each pot goes to the best hand eligible for it. Everyone's chips are already in the pot, so only the winners' balances change.
+    /// tied hands split the pot, with odd chips going to the first seats left of the dealer
+    fn award_pots<T: PartialOrd>(&mut self, hand_ranks: &[Option<T>], events: &mut Vec<Event>) {
+        let first_seat = (self.dealer + 1) % self.num_players();
+        let winnings = pot::award_pots(&self.pots(), hand_ranks, first_seat);
+        self.pay_out(&winnings, events);
+    }
+
+    /// everyone else folded, so `winner` takes every pot
+    fn transfer_pot(&mut self, winner: usize, events: &mut Vec<Event>) {
+        let hand_ranks = (0..self.num_players()).map(|i| (i == winner).then_some(())).collect::<Vec<_>>();
+        self.award_pots(&hand_ranks, events);
+    }
+
+    fn pay_out(&mut self, winnings: &[Chips], events: &mut Vec<Event>) {
+        for (player, &amount) in winnings.iter().enumerate().filter(|(_, &amount)| amount > 0) {
+            self.balances[player] += amount;
+            events.push(Event::PotWon { player, amount });
+        }
+    }
+
+    fn emit_bet(&self, player: usize, action: BetAction, events: &mut Vec<Event>) {
+        events.push(Event::Bet { player, action, amount: self.bets[player].amount() });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Action::*;
+
+    const STAKES: Stakes = Stakes { small_blind: 5, big_blind: 10, ante: None, betting: BettingStructure::NoLimit };
+
+    /// a table waiting for everyone to deal, with the button on seat 0
+    fn table(balances: &[Chips]) -> Table {
+        table_with(STAKES, balances)
+    }
+
+    fn table_with(stakes: Stakes, balances: &[Chips]) -> Table {
+        let mut table = Table::new(balances.to_vec(), stakes);
+        table.phase = Phase::DEAL;
+        table
+    }
+
+    /// a table that's just posted its blinds
+    fn preflop(balances: &[Chips]) -> Table {
+        let mut table = table(balances);
+        reveal_all(&mut table);
+        table
+    }
+
+    /// like `preflop`, but with a different betting structure
+    fn preflop_with(betting: BettingStructure, balances: &[Chips]) -> Table {
+        let mut table = table_with(Stakes { betting, ..STAKES }, balances);
+        reveal_all(&mut table);
+        table
+    }
+
+    /// everyone who still has to reveal does, which deals the hand or moves it on to the next street
+    fn reveal_all(table: &mut Table) -> Vec<Event> {
+        let mut events = Vec::new();
+        let players = (0..table.num_players()).filter(|&i| !table.revealed_players[i]).collect::<Vec<_>>();
+        for player in players {
+            events.extend(apply(table, player, Reveal).unwrap());
+        }
+        events
+    }
+
+    /// plays out `actions` in order, each of which has to be legal for the player whose turn it is
+    fn play(table: &mut Table, actions: &[(usize, Action)]) -> Vec<Event> {
+        let mut events = Vec::new();
+        for &(player, action) in actions {
+            assert_eq!(table.turn, player, "expected it to be player {}'s turn to {:?}", player, action);
+            events.extend(apply(table, player, action).unwrap_or_else(|err| panic!("player {} couldn't {:?}: {}", player, action, err)));
+        }
+        events
+    }
+
+    #[test]
+    fn shuffling_goes_around_from_the_dealer() {
+        let mut table = Table::new(vec![1000; 3], STAKES);
+        table.dealer = 1;
+        table.new_round();
+        assert!(table.between_hands());
+
+        assert_eq!(apply(&mut table, 0, Shuffle), Err(Error::NotYourTurn));
+        assert_eq!(apply(&mut table, 1, Reveal), Err(Error::WrongPhase(Phase::SHUFFLE)));
+        assert_eq!(apply(&mut table, 3, Shuffle), Err(Error::NotAPlayer));
+        for player in [1, 2, 0] {
+            assert_eq!(apply(&mut table, player, Shuffle), Ok(vec![]));
+        }
+        assert_eq!(table.phase, Phase::DEAL);
+        assert_eq!(apply(&mut table, 1, Shuffle), Err(Error::WrongPhase(Phase::DEAL)));
+
+        // everyone deals, in any order, and then the blinds go in
+        assert_eq!(apply(&mut table, 2, Reveal), Ok(vec![]));
+        assert_eq!(apply(&mut table, 2, Reveal), Err(Error::AlreadyRevealed));
+        assert_eq!(apply(&mut table, 0, Reveal), Ok(vec![]));
+        assert_eq!(apply(&mut table, 1, Reveal), Ok(vec![
+            Event::Bet { player: 2, action: BetAction::Blind, amount: 5 },
+            Event::Bet { player: 0, action: BetAction::Blind, amount: 10 },
+        ]));
+        assert_eq!(table.phase, Phase::BET0);
+        assert_eq!(table.turn, 1);
+    }
+
+    #[test]
+    fn blinds_and_preflop_action_order() {
+        let table = preflop(&[1000; 4]);
+        assert_eq!(table.balances, vec![1000, 995, 990, 1000]);
+        assert_eq!(table.street_bets, vec![0, 5, 10, 0]);
+        assert_eq!(table.current_bet, 10);
+        assert!(matches!(table.phase, Phase::BET0));
+
+        // under the gun is left of the big blind
+        assert_eq!(table.turn, 3);
+    }
+
+    #[test]
+    fn table_stakes_and_antes() {
+        let mut table = table_with(Stakes { small_blind: 10, big_blind: 20, ante: Some(2), ..STAKES }, &[1000, 1000, 1000, 1]);
+        let events = reveal_all(&mut table);
+        assert_eq!(events.iter().filter(|event| matches!(event, Event::Bet { action: BetAction::Ante, .. })).count(), 4);
+
+        // antes go in the pot but don't count towards calling
+        assert_eq!(table.balances, vec![998, 988, 978, 0]);
+        assert_eq!(table.contributions(), vec![2, 12, 22, 1]);
+        assert_eq!(table.street_bets, vec![0, 10, 20, 0]);
+        assert_eq!((table.current_bet, table.min_raise), (20, 20));
+
+        // the short stack is all-in from their ante, so the action skips them
+        assert_eq!(table.turn, 0);
+        assert_eq!(apply(&mut table, 0, Raise { to: 30 }), Err(Error::RaiseTooSmall { minimum: 40 }));
+        play(&mut table, &[(0, Call), (1, Call), (2, Check)]);
+        assert_eq!(table.phase, Phase::FLOP);
+        assert_eq!(table.balances, vec![978, 978, 978, 0]);
+    }
+
+    #[test]
+    fn short_stacks_post_what_they_have() {
+        let mut table = preflop(&[1000, 3, 7, 1000]);
+        assert_eq!(table.balances, vec![1000, 0, 0, 1000]);
+        assert!(matches!(table.bets[1], BetAmount::AllIn(3)));
+        assert!(matches!(table.bets[2], BetAmount::AllIn(7)));
+
+        // the big blind still sets the price, even though it was short
+        assert_eq!(table.current_bet, 10);
+        assert_eq!(table.turn, 3);
+        play(&mut table, &[(3, Call), (0, Call)]);
+        assert_eq!(table.phase, Phase::FLOP);
+
+        // with both blinds all-in, there's only two players left to bet
+        reveal_all(&mut table);
+        assert_eq!(table.turn, 3);
+
+        // if everyone but one player is all-in from the blinds, there's nothing to bet on at all
+        let table = preflop(&[1000, 3, 7]);
+        assert_eq!(table.phase, Phase::BET0);
+        assert_eq!(table.turn, 0);
+        let table = preflop(&[3, 7]);
+        assert_eq!(table.phase, Phase::FLOP);
+    }
+
+    #[test]
+    fn scripted_hands() {
+        struct Script {
+            name: &'static str,
+            balances: Vec<Chips>,
+            actions: Vec<(usize, Action)>,
+            phase: Phase,
+            turn: Option<usize>,
+            hand_over: bool,
+            expected_balances: Vec<Chips>,
+        }
+
+        let scripts = vec![
+            Script {
+                name: "limped pot - the big blind checks their option",
+                balances: vec![1000; 4],
+                actions: vec![(3, Call), (0, Call), (1, Call), (2, Check)],
+                phase: Phase::FLOP,
+                turn: None,
+                hand_over: false,
+                // calling only puts in the shortfall - the small blind puts in 5 more, not 10
+                expected_balances: vec![990; 4],
+            },
+            Script {
+                name: "the big blind raises their option, so everyone acts again",
+                balances: vec![1000; 4],
+                actions: vec![(3, Call), (0, Call), (1, Call), (2, Raise { to: 30 })],
+                phase: Phase::BET0,
+                turn: Some(3),
+                hand_over: false,
+                expected_balances: vec![990, 990, 970, 990],
+            },
+            Script {
+                name: "raise and re-raise",
+                balances: vec![1000; 4],
+                actions: vec![(3, Raise { to: 30 }), (0, Raise { to: 50 }), (1, Fold), (2, Fold), (3, Call)],
+                phase: Phase::FLOP,
+                turn: None,
+                hand_over: false,
+                expected_balances: vec![950, 995, 990, 950],
+            },
+            Script {
+                name: "everyone folds to the big blind",
+                balances: vec![1000; 4],
+                actions: vec![(3, Fold), (0, Fold), (1, Fold)],
+                phase: Phase::BET0,
+                turn: None,
+                hand_over: true,
+                expected_balances: vec![1000, 995, 1005, 1000],
+            },
+            Script {
+                name: "a raise takes down the blinds",
+                balances: vec![1000; 3],
+                // with three players the button is under the gun
+                actions: vec![(0, Raise { to: 25 }), (1, Fold), (2, Fold)],
+                phase: Phase::BET0,
+                turn: None,
+                hand_over: true,
+                expected_balances: vec![1015, 995, 990],
+            },
+            Script {
+                name: "calling all-in for less",
+                balances: vec![1000, 1000, 1000, 40],
+                actions: vec![(3, Call), (0, Raise { to: 100 }), (1, Fold), (2, Fold), (3, Call)],
+                phase: Phase::FLOP,
+                turn: None,
+                hand_over: false,
+                expected_balances: vec![900, 995, 990, 0],
+            },
+            Script {
+                name: "everyone's all-in preflop",
+                balances: vec![300, 200, 100],
+                actions: vec![(0, AllIn), (1, AllIn), (2, AllIn)],
+                phase: Phase::FLOP,
+                turn: None,
+                hand_over: false,
+                expected_balances: vec![0, 0, 0],
+            },
+        ];
+
+        for script in scripts {
+            let mut table = preflop(&script.balances);
+            let events = play(&mut table, &script.actions);
+            assert_eq!(table.phase, script.phase, "{}", script.name);
+            if let Some(turn) = script.turn {
+                assert_eq!(table.turn, turn, "{}", script.name);
+            }
+            assert_eq!(events.last() == Some(&Event::HandOver), script.hand_over, "{}", script.name);
+            assert_eq!(table.balances, script.expected_balances, "{}", script.name);
+        }
+    }
+
+    #[test]
+    fn heads_up() {
+        let mut table = table(&[1000, 1000]);
+
+        // the dealer posts the small blind and acts first before the flop
+        reveal_all(&mut table);
+        assert_eq!(table.balances, vec![995, 990]);
+        assert_eq!(table.turn, 0);
+
+        // the big blind still gets their option
+        play(&mut table, &[(0, Call)]);
+        assert_eq!(table.phase, Phase::BET0);
+        play(&mut table, &[(1, Raise { to: 30 }), (0, Call)]);
+        assert_eq!(table.phase, Phase::FLOP);
+
+        // and the dealer acts last after it
+        reveal_all(&mut table);
+        assert_eq!(table.turn, 1);
+        let events = play(&mut table, &[(1, Check), (0, Bet(20)), (1, Fold)]);
+        assert_eq!(events[2..], [
+            Event::Bet { player: 1, action: BetAction::Fold, amount: 30 },
+            Event::PotWon { player: 0, amount: 80 },
+            Event::HandOver,
+        ]);
+        assert_eq!(table.balances, vec![1030, 970]);
+    }
+
+    #[test]
+    fn min_raise_is_the_last_raise_size() {
+        let mut table = preflop(&[1000; 4]);
+
+        // the first raise has to be at least a big blind on top of the big blind
+        assert_eq!(apply(&mut table, 3, Raise { to: 15 }), Err(Error::RaiseTooSmall { minimum: 20 }));
+        play(&mut table, &[(3, Raise { to: 30 })]);
+
+        // that was a raise of 20, so the next one has to be to at least 50
+        assert_eq!(apply(&mut table, 0, Raise { to: 45 }), Err(Error::RaiseTooSmall { minimum: 50 }));
+        play(&mut table, &[(0, Raise { to: 50 })]);
+        assert_eq!(table.min_raise, 20);
+
+        play(&mut table, &[(1, Raise { to: 150 })]);
+        assert_eq!(table.min_raise, 100);
+        assert_eq!(apply(&mut table, 2, Raise { to: 200 }), Err(Error::RaiseTooSmall { minimum: 250 }));
+
+        // you can't raise more than you have
+        assert_eq!(apply(&mut table, 2, Raise { to: 1001 }), Err(Error::InsufficientBalance { balance: 990 }));
+    }
+
+    #[test]
+    fn pot_limit() {
+        let mut table = preflop_with(BettingStructure::PotLimit, &[1000; 4]);
+
+        // calling 10 makes the pot 25, so under the gun can raise by at most 25
+        assert_eq!(apply(&mut table, 3, Raise { to: 36 }), Err(Error::RaiseTooBig { maximum: 35 }));
+        assert_eq!(apply(&mut table, 3, AllIn), Err(Error::IllegalAction(BetAction::AllIn)));
+        play(&mut table, &[(3, Raise { to: 35 })]);
+
+        // calling 35 makes the pot 85, so the button can raise to at most 120
+        assert_eq!(apply(&mut table, 0, Raise { to: 121 }), Err(Error::RaiseTooBig { maximum: 120 }));
+        assert_eq!(apply(&mut table, 0, Raise { to: 50 }), Err(Error::RaiseTooSmall { minimum: 60 }));
+        play(&mut table, &[(0, Raise { to: 120 }), (1, Fold), (2, Fold), (3, Call)]);
+        assert_eq!(table.phase, Phase::FLOP);
+
+        // a bet is capped at the pot
+        reveal_all(&mut table);
+        assert_eq!(apply(&mut table, 3, Bet(256)), Err(Error::RaiseTooBig { maximum: 255 }));
+        play(&mut table, &[(3, Bet(255))]);
+
+        // the pot's big enough by now that the button can get it all in
+        play(&mut table, &[(0, AllIn), (3, Call)]);
+        assert_eq!(table.phase, Phase::TURN);
+        assert_eq!(table.balances, vec![0, 995, 990, 0]);
+    }
+
+    #[test]
+    fn fixed_limit() {
+        let mut table = preflop_with(BettingStructure::FixedLimit, &[1000; 4]);
+
+        // preflop and on the flop, raises are exactly one big blind
+        assert_eq!(apply(&mut table, 3, Raise { to: 25 }), Err(Error::RaiseTooBig { maximum: 20 }));
+        assert_eq!(apply(&mut table, 3, Raise { to: 15 }), Err(Error::RaiseTooSmall { minimum: 20 }));
+        assert_eq!(apply(&mut table, 3, AllIn), Err(Error::IllegalAction(BetAction::AllIn)));
+
+        // the big blind counts as the first bet, so the betting's capped after three raises
+        play(&mut table, &[(3, Raise { to: 20 }), (0, Raise { to: 30 }), (1, Raise { to: 40 })]);
+        assert_eq!(table.num_bets, 4);
+        assert_eq!(apply(&mut table, 2, Raise { to: 50 }), Err(Error::IllegalAction(BetAction::Raise)));
+        play(&mut table, &[(2, Call), (3, Call), (0, Call)]);
+        assert_eq!(table.phase, Phase::FLOP);
+
+        reveal_all(&mut table);
+        assert_eq!(apply(&mut table, 1, Bet(20)), Err(Error::RaiseTooBig { maximum: 10 }));
+        play(&mut table, &[(1, Bet(10)), (2, Call), (3, Call), (0, Call)]);
+        assert_eq!(table.phase, Phase::TURN);
+
+        // on the turn and river the bet size doubles
+        reveal_all(&mut table);
+        assert_eq!(apply(&mut table, 1, Bet(10)), Err(Error::RaiseTooSmall { minimum: 20 }));
+        play(&mut table, &[(1, Bet(20)), (2, Raise { to: 40 }), (3, Fold), (0, Fold), (1, Call)]);
+        assert_eq!(table.phase, Phase::RIVER);
+        assert_eq!(table.balances, vec![950, 910, 910, 950]);
+    }
+
+    #[test]
+    fn fixed_limit_short_all_in() {
+        let mut table = preflop_with(BettingStructure::FixedLimit, &[1000, 1000, 1000, 15]);
+
+        // seat 3 can't make a full raise, but can still get their last chips in
+        play(&mut table, &[(3, AllIn)]);
+        assert_eq!((table.current_bet, table.num_bets), (15, 1));
+        play(&mut table, &[(0, Raise { to: 25 }), (1, Fold), (2, Call)]);
+        assert_eq!(table.phase, Phase::FLOP);
+    }
+
+    #[test]
+    fn illegal_actions() {
+        let mut table = preflop(&[1000; 4]);
+
+        // the big blind is a bet, so there's nothing to check and nothing to open
+        assert_eq!(apply(&mut table, 3, Check), Err(Error::IllegalAction(BetAction::Check)));
+        assert_eq!(apply(&mut table, 3, Bet(20)), Err(Error::IllegalAction(BetAction::Bet)));
+        assert_eq!(apply(&mut table, 0, Call), Err(Error::NotYourTurn));
+        assert_eq!(apply(&mut table, 3, Reveal), Err(Error::WrongPhase(Phase::BET0)));
+
+        play(&mut table, &[(3, Call), (0, Call), (1, Call)]);
+
+        // the big blind has nothing to call
+        assert_eq!(apply(&mut table, 2, Call), Err(Error::IllegalAction(BetAction::Call)));
+
+        play(&mut table, &[(2, Check)]);
+        assert_eq!(apply(&mut table, 2, Check), Err(Error::WrongPhase(Phase::FLOP)));
+    }
+
+    #[test]
+    fn postflop_action_starts_left_of_the_dealer() {
+        let mut table = preflop(&[1000; 4]);
+        play(&mut table, &[(3, Call), (0, Call), (1, Fold), (2, Check)]);
+        assert_eq!(table.phase, Phase::FLOP);
+
+        // the player who folded still has to help reveal the board
+        assert_eq!(table.revealed_players, vec![false; 4]);
+        reveal_all(&mut table);
+        assert_eq!(table.phase, Phase::BET1);
+        assert_eq!(table.street_bets, vec![0; 4]);
+        assert_eq!(table.current_bet, 0);
+
+        // the small blind folded, so the big blind is first
+        assert_eq!(table.turn, 2);
+
+        // checked around
+        play(&mut table, &[(2, Check), (3, Check), (0, Check)]);
+        assert_eq!(table.phase, Phase::TURN);
+
+        reveal_all(&mut table);
+        play(&mut table, &[(2, Bet(20)), (3, Raise { to: 60 }), (0, Fold), (2, Call)]);
+        assert_eq!(table.phase, Phase::RIVER);
+        assert_eq!(table.balances, vec![990, 995, 930, 930]);
+    }
+
+    #[test]
+    fn short_all_in_does_not_reopen_the_betting() {
+        let mut table = preflop(&[1000, 1000, 1000, 50]);
+        play(&mut table, &[(3, Call), (0, Call), (1, Call), (2, Check)]);
+        reveal_all(&mut table);
+
+        // seat 3 has 40 left. Going all-in over a bet of 30 is only a raise of 10
+        play(&mut table, &[(1, Bet(30)), (2, Call), (3, AllIn)]);
+        assert_eq!(table.current_bet, 40);
+        assert_eq!(table.min_raise, 30);
+
+        // seat 0 hasn't acted yet, so they can still raise
+        assert_eq!(table.check(0, Raise { to: 70 }), Ok(()));
+        play(&mut table, &[(0, Call)]);
+
+        // seats 1 and 2 already acted, so they can only call the extra 10 or fold
+        assert_eq!(apply(&mut table, 1, Raise { to: 100 }), Err(Error::IllegalAction(BetAction::Raise)));
+        assert_eq!(apply(&mut table, 1, AllIn), Err(Error::IllegalAction(BetAction::AllIn)));
+        play(&mut table, &[(1, Call), (2, Call)]);
+        assert_eq!(table.phase, Phase::TURN);
+        assert_eq!(table.balances, vec![950, 950, 950, 0]);
+    }
+
+    #[test]
+    fn betting_is_skipped_once_nobody_can_bet() {
+        let mut table = preflop(&[1000, 1000, 1000, 100]);
+        play(&mut table, &[(3, AllIn), (0, Call), (1, Fold), (2, Fold)]);
+        assert_eq!(table.phase, Phase::FLOP);
+
+        // seat 0 is the only one left who isn't all-in, so there's nothing to bet on the flop
+        reveal_all(&mut table);
+        assert_eq!(table.phase, Phase::TURN);
+    }
+
+    #[test]
+    fn showdown() {
+        let mut table = preflop(&[1000; 4]);
+        play(&mut table, &[(3, Call), (0, Fold), (1, Fold), (2, Check)]);
+        for _ in 0..3 {
+            reveal_all(&mut table);
+            play(&mut table, &[(2, Check), (3, Check)]);
+        }
+
+        // folded players don't show their cards
+        assert_eq!(table.phase, Phase::SHOWDOWN_REVEAL);
+        assert_eq!(table.revealed_players, vec![true, true, false, false]);
+        assert_eq!(apply(&mut table, 0, Reveal), Err(Error::AlreadyRevealed));
+        reveal_all(&mut table);
+        assert_eq!(table.phase, Phase::SHOWDOWN);
+
+        // the two hands tie, and the odd chip goes to the first of them left of the dealer
+        let events = table.showdown(&[None, None, Some(7), Some(7)]);
+        assert_eq!(events, vec![Event::PotWon { player: 2, amount: 13 }, Event::PotWon { player: 3, amount: 12 }, Event::HandOver]);
+        assert_eq!(table.balances, vec![1000, 995, 1003, 1002]);
+    }
+
+    #[test]
+    fn players_not_dealt_in_are_skipped() {
+        let mut table = Table::new(vec![1000; 4], STAKES);
+        table.dealt_in[1] = false;
+        table.new_round();
+        assert_eq!(table.revealed_players, vec![false, true, false, false]);
+        assert!(table.player_is_folded(1));
+
+        for player in [0, 2, 3] {
+            apply(&mut table, player, Shuffle).unwrap();
+        }
+        reveal_all(&mut table);
+
+        // the blinds skip seat 1, and so does the action
+        assert_eq!(table.contributions(), vec![0, 0, 5, 10]);
+        assert_eq!(table.turn, 0);
+        play(&mut table, &[(0, Call), (2, Call), (3, Check)]);
+        assert_eq!(table.revealed_players, vec![false, true, false, false]);
+    }
+
+    #[test]
+    fn slashing_splits_the_stallers_chips() {
+        let mut table = preflop(&[1000, 1000, 1001, 1000]);
+        play(&mut table, &[(3, Call), (0, Call), (1, Call), (2, Check)]);
+
+        // everyone gets their bets back, and seat 2's chips are split between everyone else, odd chip first left of the dealer
+        let events = table.slash(&[2]).unwrap();
+        assert_eq!(events, vec![
+            Event::PotWon { player: 0, amount: 333 },
+            Event::PotWon { player: 1, amount: 334 },
+            Event::PotWon { player: 3, amount: 334 },
+        ]);
+        assert_eq!(table.balances, vec![1333, 1334, 0, 1334]);
+        assert_eq!(table.contributions(), vec![0; 4]);
+    }
+
+    #[test]
+    fn slashing_everyone_in_the_hand_pays_the_players_sitting_out() {
+        let mut table = Table::new(vec![1000, 1000, 1001, 1000], STAKES);
+        table.dealt_in[1] = false;
+        table.dealt_in[3] = false;
+        table.new_round();
+        for player in [0, 2] {
+            apply(&mut table, player, Shuffle).unwrap();
+        }
+        assert_eq!(table.phase, Phase::DEAL);
+
+        // there has to be someone to pay, and the table's left alone if there isn't
+        let before = table.clone();
+        assert_eq!(table.slash(&[0, 1, 2, 3]), Err(Error::NobodyToPay));
+        assert_eq!(table.slash(&[0, 4]), Err(Error::NotAPlayer));
+        assert_eq!(table, before);
+
+        // nobody dealt in sends their tokens, so the players sitting the hand out split the lot
+        let events = table.slash(&[0, 2]).unwrap();
+        assert_eq!(events, vec![
+            Event::PotWon { player: 1, amount: 1001 },
+            Event::PotWon { player: 3, amount: 1000 },
+        ]);
+        assert_eq!(table.balances, vec![0, 2001, 0, 2000]);
+    }
+
+    /// plays a few thousand random moves - most of them illegal - checking that rejected moves don't change anything
+    /// and that no chips are ever created or lost
+    #[test]
+    fn random_play() {
+        // a small xorshift, so the test is the same every run without pulling in `rand`
+        let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = |bound: u64| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed % bound
+        };
+
+        let balances = [1000, 40, 730, 5000, 12];
+        let total: Chips = balances.iter().sum();
+        for betting in [BettingStructure::NoLimit, BettingStructure::PotLimit, BettingStructure::FixedLimit] {
+            let mut table = Table::new(balances.to_vec(), Stakes { ante: Some(1), betting, ..STAKES });
+            let mut hands = 0;
+            for _ in 0..20000 {
+                // usually whoever's turn it is, so that hands actually get played. Anyone can reveal, in any order
+                let anyone = next(table.num_players() as u64 + 1) as usize;
+                let actor = if table.phase.is_revealing() || next(4) == 0 { anyone } else { table.turn };
+                let amount = next(table.current_bet as u64 * 3 + 50) as Chips;
+                let action = [Shuffle, Reveal, Check, Call, Bet(amount), Raise { to: amount }, AllIn, Fold][next(8) as usize];
+
+                let before = table.clone();
+                let mut events = match apply(&mut table, actor, action) {
+                    Ok(events) => events,
+                    Err(_) => {
+                        assert_eq!(table, before, "{:?} by {} was rejected but changed the table", action, actor);
+                        continue;
+                    },
+                };
+                if table.phase == Phase::SHOWDOWN {
+                    let hand_ranks = (0..table.num_players()).map(|i| (!table.player_is_folded(i)).then(|| next(3))).collect::<Vec<_>>();
+                    events.extend(table.showdown(&hand_ranks));
+                }
+
+                if events.last() != Some(&Event::HandOver) {
+                    assert_eq!(table.balances.iter().sum::<Chips>() + table.contributions().iter().sum::<Chips>(), total);
+                    continue;
+                }
+
+                // the pots are paid out - on to the next hand, with everyone who has chips left
+                assert_eq!(table.balances.iter().sum::<Chips>(), total);
+                hands += 1;
+                table.dealt_in = table.balances.iter().map(|&balance| balance > 0).collect();
+                if table.num_dealt_in() < 2 {
+                    // someone's won everything, so everyone buys back in
+                    table = Table::new(balances.to_vec(), table.stakes);
+                    continue;
+                }
+                table.dealer = table.next_dealt_in(table.dealer);
+                table.phase = Phase::SHUFFLE;
+                table.new_round();
+            }
+            assert!(hands > 20, "only {} hands were played with {:?}", hands, betting);
+        }
+    }
+}
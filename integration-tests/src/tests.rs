@@ -2,19 +2,22 @@ use std::{env, fs};
 use near_units::parse_near;
 use serde_json::json;
 use workspaces::{Account, Contract};
+use workspaces::result::{ExecutionFinalResult, ExecutionSuccess};
 use rand::{Rng, thread_rng};
 use ark_ff::One;
-use ark_ec::ProjectiveCurve;
 use barnett_smart_card_protocol::{
 	BarnettSmartProtocol,
 	discrete_log_cards::{
 		BnParameters,
 		BnParamsBuf,
-		BnCardProtocol, BnPublicKeyBuf, BnPlayerSecretKey, BnPlayerSecretKeyBuf, BnZKProofKeyOwnershipBuf, get_card_elems_buf, BnScalar, BnMaskedCardBuf
+		BnCardProtocol, BnPublicKey, BnPublicKeyBuf, BnPlayerSecretKey, BnZKProofKeyOwnershipBuf, get_card_elems_buf, BnScalar, BnMaskedCardBuf, BnZKProofMaskingBuf,
+		BnShuffleOutputBuf, BnRevealTokenWithProofBuf
 	}
 };
+use proof_essentials::utils::{permutation::Permutation, rand::sample_vector};
 
 const NUM_PLAYERS: usize = 4;
+const BUY_IN: u128 = parse_near!("1 N");
 const M: usize = 2;
 const N: usize = 26;
 
@@ -46,178 +49,1100 @@ async fn main() -> anyhow::Result<()> {
     }
 
     // begin tests
-    test_one_round(&players, &contract, &params).await?;
+    test_state_persists_across_calls(&players, &contract, &params).await?;
+    test_buy_in_and_leave(&players, &contract, &params).await?;
+    test_init_deck_rejects_invalid_masking(&players, &contract, &params).await?;
+    test_derive_initial_deck(&players, &contract, &params).await?;
+    test_claim_timeout(&players, &contract, &params).await?;
+    test_heads_up(&players, &contract, &params).await?;
+    test_seat_management(&players, &contract, &params).await?;
+    test_four_handed(&players, &contract, &params).await?;
+    test_cheating_is_rejected(&players, &contract, &params).await?;
     Ok(())
 }
 
-async fn test_one_round(
+/// the code of the `ContractError` a failed call panicked with, e.g. `NOT_YOUR_TURN`.
+/// The contract panics with `ERR_JSON:{"code": ..., ...}`, which ends up (escaped) somewhere in the failure
+fn error_code(outcome: &ExecutionFinalResult) -> Option<String> {
+    let failure = format!("{:?}", outcome.failures()).replace("\\\"", "\"");
+    let (_, err) = failure.split_once("ERR_JSON:")?;
+    let (_, code) = err.split_once("\"code\":\"")?;
+    code.split('"').next().map(str::to_string)
+}
+
+async fn test_state_persists_across_calls(
     players: &[Account],
     contract: &Contract,
     params: &BnParameters,
 ) -> anyhow::Result<()> {
     let alice = &players[0];
     let bob = &players[1];
-    let carol = &players[2];
-    let dave = &players[3];
 
-	let mut rng = thread_rng();
+    let mut rng = thread_rng();
 
-    // everyone generates game keys
-    let (alice_pk, alice_sk) = BnCardProtocol::player_keygen(&mut rng, params).unwrap();
-    let (bob_pk, bob_sk) = BnCardProtocol::player_keygen(&mut rng, params).unwrap();
-    let (carol_pk, carol_sk) = BnCardProtocol::player_keygen(&mut rng, params).unwrap();
-    let (dave_pk, dave_sk) = BnCardProtocol::player_keygen(&mut rng, params).unwrap();
+    let alice_key = game_key(&mut rng, params, alice);
+    let bob_key = game_key(&mut rng, params, bob);
+    let game_id = create_game(alice, contract, &alice_key, json!({})).await?;
+    let outcome = join_game(bob, contract, game_id, &bob_key).await?;
 
-    let alice_pk_buf = BnPublicKeyBuf::serialize(alice_pk).unwrap();
-    let alice_sk_buf = BnPlayerSecretKeyBuf::serialize(alice_sk).unwrap();
-    let bob_pk_buf = BnPublicKeyBuf::serialize(bob_pk).unwrap();
-    let bob_sk_buf = BnPlayerSecretKeyBuf::serialize(bob_sk).unwrap();
-    let carol_pk_buf = BnPublicKeyBuf::serialize(carol_pk).unwrap();
-    let carol_sk_buf = BnPlayerSecretKeyBuf::serialize(carol_sk).unwrap();
-    let dave_pk_buf = BnPublicKeyBuf::serialize(dave_pk).unwrap();
-    let dave_sk_buf = BnPlayerSecretKeyBuf::serialize(dave_sk).unwrap();
+    // joining is logged as a NEP-297 event
+    let events = outcome.logs().into_iter()
+        .filter_map(|log| log.strip_prefix("EVENT_JSON:"))
+        .map(serde_json::from_str::<serde_json::Value>)
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(events, vec![json!({
+        "standard": "rainbase",
+        "version": "1.0.0",
+        "event": "player_joined",
+        "data": {
+            "game_id": game_id,
+            "account_id": bob.id(),
+            "chips": "1000",
+        },
+    })]);
 
+    // bob is only rejected the second time if the first join was written back
+    let args = json!({ "game_id": game_id, "pk": bob_key.pk_buf, "key_ownership_proof": bob_key.key_proof });
+    assert_eq!(rejection(bob, contract, "join_game", args, BUY_IN).await?, "ALREADY_JOINED");
 
-    // everyone generates key ownership proofs
-    let alice_key_proof = BnCardProtocol::prove_key_ownership(&mut rng, params, &alice_pk, &alice_sk, alice.id().as_bytes()).unwrap();
-    let bob_key_proof = BnCardProtocol::prove_key_ownership(&mut rng, params, &bob_pk, &bob_sk, bob.id().as_bytes()).unwrap();
-    let carol_key_proof = BnCardProtocol::prove_key_ownership(&mut rng, params, &carol_pk, &carol_sk, carol.id().as_bytes()).unwrap();
-    let dave_key_proof = BnCardProtocol::prove_key_ownership(&mut rng, params, &dave_pk, &dave_sk, dave.id().as_bytes()).unwrap();
+    call(alice, contract, "start_game", json!({ "game_id": game_id })).await?;
 
-    let alice_key_proof_buf = BnZKProofKeyOwnershipBuf::serialize(alice_key_proof).unwrap();
-    let bob_key_proof_buf = BnZKProofKeyOwnershipBuf::serialize(bob_key_proof).unwrap();
-    let carol_key_proof_buf = BnZKProofKeyOwnershipBuf::serialize(carol_key_proof).unwrap();
-    let dave_key_proof_buf = BnZKProofKeyOwnershipBuf::serialize(dave_key_proof).unwrap();
+    let agg_pk_buf: BnPublicKeyBuf = contract
+        .view("get_aggregate_pubkey", json!({ "game_id": game_id }).to_string().into_bytes())
+        .await?
+        .json()?;
+    let agg_pk = agg_pk_buf.deserialize().unwrap();
 
-    let _alice_pk = alice_pk_buf.deserialize().unwrap();
-    let _alice_proof = alice_key_proof_buf.deserialize().unwrap();
+    let (deck_buf, proofs_buf) = mask_deck(&mut rng, params, &agg_pk);
 
-    let params_buf = BnParamsBuf::serialize(params.clone()).unwrap();
-    let _pp = params_buf.deserialize().unwrap();
+    let valid: bool = alice.call(contract.id(), "init_deck")
+        .gas(near_units::parse_gas!("300 T") as u64)
+        .args_json(json!({
+            "game_id": game_id,
+            "deck": deck_buf,
+            "masking_proofs": proofs_buf,
+        }))
+        .transact()
+        .await?
+        .json()?;
+    assert!(valid);
+
+    // the deck can only be initialized once, so this fails iff the first call persisted
+    let reinit = alice.call(contract.id(), "init_deck")
+        .gas(near_units::parse_gas!("300 T") as u64)
+        .args_json(json!({
+            "game_id": game_id,
+            "deck": deck_buf,
+            "masking_proofs": proofs_buf,
+        }))
+        .transact()
+        .await?;
+    assert!(reinit.is_failure(), "initializing the deck twice should fail once the deck has been persisted");
+
+    // the rest of the masking proofs get verified a batch at a time, and verification progress persists too
+    for _ in 0..3 {
+        let valid: bool = bob.call(contract.id(), "verify_initial_deck")
+            .gas(near_units::parse_gas!("300 T") as u64)
+            .args_json(json!({ "game_id": game_id }))
+            .transact()
+            .await?
+            .json()?;
+        assert!(valid);
+    }
+
+    let reverify = bob.call(contract.id(), "verify_initial_deck")
+        .gas(near_units::parse_gas!("300 T") as u64)
+        .args_json(json!({ "game_id": game_id }))
+        .transact()
+        .await?;
+    assert!(reverify.is_failure(), "verifying a fully verified deck should fail");
+
+    println!("      Passed ✅ game state persists across calls");
+    Ok(())
+}
+
+async fn test_init_deck_rejects_invalid_masking(
+    players: &[Account],
+    contract: &Contract,
+    params: &BnParameters,
+) -> anyhow::Result<()> {
+    let alice = &players[0];
 
-    // alice creates a game
-    let game_id: [u8; 4] = alice.call(contract.id(), "create_game")
+    let mut rng = thread_rng();
+
+    let table = Table::start(&[alice, &players[1]], contract, params, &mut rng).await?;
+    let game_id = table.game_id;
+
+    let agg_pk_buf: BnPublicKeyBuf = contract
+        .view("get_aggregate_pubkey", json!({ "game_id": game_id }).to_string().into_bytes())
+        .await?
+        .json()?;
+    let agg_pk = agg_pk_buf.deserialize().unwrap();
+
+    // a deck with the first card in it twice - the second copy is a valid masking, just of the wrong card
+    let (mut deck_buf, mut proofs_buf) = mask_deck(&mut rng, params, &agg_pk);
+    deck_buf[1] = deck_buf[0].clone();
+    proofs_buf[1] = proofs_buf[0].clone();
+
+    let valid: bool = alice.call(contract.id(), "init_deck")
         .gas(near_units::parse_gas!("300 T") as u64)
         .args_json(json!({
-            "creator_pk": alice_pk_buf,
-            "creator_key_ownership_proof": alice_key_proof_buf,
+            "game_id": game_id,
+            "deck": deck_buf,
+            "masking_proofs": proofs_buf,
         }))
         .transact()
         .await?
         .json()?;
+    assert!(!valid, "a deck with a duplicate card should be rejected");
+
+    // the bad deck was thrown out, so nobody can shuffle it, but the creator can submit a new one
+    let (deck_buf, proofs_buf) = mask_deck(&mut rng, params, &agg_pk);
+    let valid: bool = alice.call(contract.id(), "init_deck")
+        .gas(near_units::parse_gas!("300 T") as u64)
+        .args_json(json!({
+            "game_id": game_id,
+            "deck": deck_buf,
+            "masking_proofs": proofs_buf,
+        }))
+        .transact()
+        .await?
+        .json()?;
+    assert!(valid);
+
+    println!("      Passed ✅ init_deck rejects invalid maskings");
+    Ok(())
+}
+
+async fn test_derive_initial_deck(
+    players: &[Account],
+    contract: &Contract,
+    params: &BnParameters,
+) -> anyhow::Result<()> {
+    let alice = &players[0];
+    let bob = &players[1];
+
+    let mut rng = thread_rng();
+
+    let table = Table::open(&[alice, bob], contract, params, &mut rng, json!({})).await?;
+    let game_id = table.game_id;
+
+    // the deck can't be derived before there's an aggregate key
+    let early = bob.call(contract.id(), "derive_initial_deck")
+        .gas(near_units::parse_gas!("300 T") as u64)
+        .args_json(json!({ "game_id": game_id }))
+        .transact()
+        .await?;
+    assert!(early.is_failure(), "deriving the deck before the game starts should fail");
+
+    table.start_game().await?;
+
+    // anyone at the table can derive it, not just the creator. There are no masking proofs to build, so it's cheap
+    let derived = bob.call(contract.id(), "derive_initial_deck")
+        .gas(near_units::parse_gas!("300 T") as u64)
+        .args_json(json!({ "game_id": game_id }))
+        .transact()
+        .await?;
+    let gas_burnt = derived.total_gas_burnt;
+    derived.into_result()?;
+    assert!(gas_burnt < near_units::parse_gas!("100 T") as u64, "deriving the deck burnt {} gas", gas_burnt);
+
+    let rederive = alice.call(contract.id(), "derive_initial_deck")
+        .gas(near_units::parse_gas!("300 T") as u64)
+        .args_json(json!({ "game_id": game_id }))
+        .transact()
+        .await?;
+    assert!(rederive.is_failure(), "deriving the deck twice should fail");
+
+    // and since it's already been derived, the creator can't swap in a deck of their own
+    let agg_pk_buf: BnPublicKeyBuf = contract
+        .view("get_aggregate_pubkey", json!({ "game_id": game_id }).to_string().into_bytes())
+        .await?
+        .json()?;
+    let (deck_buf, proofs_buf) = mask_deck(&mut rng, params, &agg_pk_buf.deserialize().unwrap());
+    let init = alice.call(contract.id(), "init_deck")
+        .gas(near_units::parse_gas!("300 T") as u64)
+        .args_json(json!({
+            "game_id": game_id,
+            "deck": deck_buf,
+            "masking_proofs": proofs_buf,
+        }))
+        .transact()
+        .await?;
+    assert!(init.is_failure(), "init_deck should fail once the deck has been derived");
+
+    println!("      Passed ✅ initial deck is derived on-chain");
+    Ok(())
+}
+
+async fn test_claim_timeout(
+    players: &[Account],
+    contract: &Contract,
+    params: &BnParameters,
+) -> anyhow::Result<()> {
+    let alice = &players[0];
+    let bob = &players[1];
+    let carol = &players[2];
+
+    let mut rng = thread_rng();
 
-    // // bob joins the game
-    // bob.call(contract.id(), "join_game")
-    //     .gas(near_units::parse_gas!("300 T") as u64)
-    //     .args_json(json!({
-    //         "game_id": game_id,
-    //         "pk": bob_pk_buf,
-    //         "key_ownership_proof": bob_key_proof_buf,
-    //     }))
-    //     .transact()
-    //     .await?
-    //     .into_result()?;
-
-    // // carol joins the game
-    // carol.call(contract.id(), "join_game")
-    //     .gas(near_units::parse_gas!("300 T") as u64)
-    //     .args_json(json!({
-    //         "game_id": game_id,
-    //         "pk": carol_pk_buf,
-    //         "key_ownership_proof": carol_key_proof_buf,
-    //     }))
-    //     .transact()
-    //     .await?
-    //     .into_result()?;
-    
-    // // dave joins the game
-    // dave.call(contract.id(), "join_game")
-    //     .gas(near_units::parse_gas!("300 T") as u64)
-    //     .args_json(json!({
-    //         "game_id": game_id,
-    //         "pk": dave_pk_buf,
-    //         "key_ownership_proof": dave_key_proof_buf,
-    //     }))
-    //     .transact()
-    //     .await?
-    //     .into_result()?;
-  
-    // // alice starts the game
-    // alice.call(contract.id(), "start_game")
-    //     .gas(near_units::parse_gas!("300 T") as u64)
-    //     .args_json(json!({
-    //         "game_id": game_id,
-    //     }))
-    //     .transact()
-    //     .await?
-    //     .into_result()?;
-    
-    // // get the aggregate key
-    // let agg_pk_buf: BnPublicKeyBuf = alice.call(contract.id(), "get_aggregate_pk")
-    //     .gas(near_units::parse_gas!("300 T") as u64)
-    //     .args_json(json!({
-    //         "game_id": game_id,
-    //     }))
-    //     .transact()
-    //     .await?
-    //     .json()?;
-    // let agg_pk = BnPublicKeyBuf::deserialize(&agg_pk_buf).unwrap();
-    
-    // // alice inits the deck
-    // // ignore the masking proofs for now, it's too much gas
-    // let cards_buf = get_card_elems_buf(52).unwrap();
-    // let cards = cards_buf.into_iter().map(|c| c.deserialize().unwrap()).map(|c| {
-    //     BnCardProtocol::mask(&mut rng, params, &agg_pk, &c, &BnScalar::one()).unwrap()
-    // }).map(|(card, _proof)| card).collect::<Vec<_>>();
-    // let cards_buf = cards.iter().cloned().map(|c| BnMaskedCardBuf::serialize(c).unwrap()).collect::<Vec<_>>();
-
-    // alice.call(contract.id(), "init_deck")
-    //     .gas(near_units::parse_gas!("300 T") as u64)
-    //     .args_json(json!({
-    //         "game_id": game_id,
-    //         "cards": cards_buf,
-    //     }))
-    //     .transact()
-    //     .await?
-    //     .into_result()?;
+    // one table with the default deadlines, and one where every deadline has already passed by the next block
+    let mut game_ids = Vec::new();
+    for timeouts in [None, Some(json!({ "shuffle": 0, "deal": 0, "bet": 0, "reveal": 0 }))] {
+        let table = Table::open(&[alice, bob, carol], contract, params, &mut rng, json!({ "timeouts": timeouts })).await?;
+        table.start_game().await?;
+        call(bob, contract, "derive_initial_deck", json!({ "game_id": table.game_id })).await?;
+        game_ids.push(table.game_id);
+    }
+
+    // it's alice's turn to shuffle on both tables
+
+    let early = bob.call(contract.id(), "claim_timeout")
+        .gas(near_units::parse_gas!("300 T") as u64)
+        .args_json(json!({ "game_id": game_ids[0] }))
+        .transact()
+        .await?;
+    assert!(early.is_failure(), "claiming a timeout before the deadline should fail");
+    assert_eq!(error_code(&early).as_deref(), Some("DEADLINE_NOT_PASSED"));
+
+    let own = alice.call(contract.id(), "claim_timeout")
+        .gas(near_units::parse_gas!("300 T") as u64)
+        .args_json(json!({ "game_id": game_ids[1] }))
+        .transact()
+        .await?;
+    assert!(own.is_failure(), "the staller shouldn't be able to claim a timeout on themselves");
+    assert_eq!(error_code(&own).as_deref(), Some("WAITING_ON_YOU"));
+
+    let alice_balance_before = alice.view_account().await?.balance;
+
+    bob.call(contract.id(), "claim_timeout")
+        .gas(near_units::parse_gas!("300 T") as u64)
+        .args_json(json!({ "game_id": game_ids[1] }))
+        .transact()
+        .await?
+        .into_result()?;
 
+    // nothing was at stake yet, so alice is sent home with her chips
+    let alice_balance_after = alice.view_account().await?.balance;
+    assert!(alice_balance_after > alice_balance_before + BUY_IN / 2, "a player ejected while shuffling should get their chips back");
+
+    // and she's no longer at the table, so she can't claim anything on it
+    let ejected = alice.call(contract.id(), "claim_timeout")
+        .gas(near_units::parse_gas!("300 T") as u64)
+        .args_json(json!({ "game_id": game_ids[1] }))
+        .transact()
+        .await?;
+    assert!(ejected.is_failure(), "an ejected player shouldn't be able to act on the table");
+    assert_eq!(error_code(&ejected).as_deref(), Some("NOT_A_PLAYER"));
+
+    println!("      Passed ✅ stalled players can be timed out");
+    Ok(())
+}
+
+/// calls `method` as `player`, failing the test if the call fails
+async fn call(player: &Account, contract: &Contract, method: &str, args: serde_json::Value) -> anyhow::Result<()> {
+    player.call(contract.id(), method)
+        .gas(near_units::parse_gas!("300 T") as u64)
+        .args_json(args)
+        .transact()
+        .await?
+        .into_result()?;
     Ok(())
 }
 
-async fn test_default_message(
-    user: &Account,
+async fn get_game(contract: &Contract, game_id: [u8; 4]) -> anyhow::Result<serde_json::Value> {
+    let game = contract
+        .view("get_game", json!({ "game_id": game_id }).to_string().into_bytes())
+        .await?
+        .json()?;
+    Ok(game)
+}
+
+async fn get_deck(contract: &Contract, game_id: [u8; 4]) -> anyhow::Result<Vec<BnMaskedCardBuf>> {
+    let deck: Option<Vec<BnMaskedCardBuf>> = contract
+        .view("get_deck", json!({ "game_id": game_id }).to_string().into_bytes())
+        .await?
+        .json()?;
+    Ok(deck.expect("the deck should have been initialized"))
+}
+
+/// shuffles and remasks `deck` under the aggregate key, along with a proof that it's a permutation of the original
+fn shuffle<R: Rng>(rng: &mut R, params: &BnParameters, agg_pk: &BnPublicKey, deck: &[BnMaskedCardBuf]) -> BnShuffleOutputBuf {
+    let deck = deck.iter().map(|c| c.deserialize().unwrap()).collect::<Vec<_>>();
+    let permutation = Permutation::new(rng, M * N);
+    let masking_factors: Vec<BnScalar> = sample_vector(rng, M * N);
+    let output = BnCardProtocol::shuffle_and_remask(rng, params, agg_pk, &deck, &permutation, &masking_factors).unwrap();
+    BnShuffleOutputBuf::serialize(output).unwrap()
+}
+
+/// a player's reveal tokens for the cards at `card_indices`, each with a proof it was computed with their key
+fn reveal_tokens<R: Rng>(
+    rng: &mut R,
+    params: &BnParameters,
+    pk: &BnPublicKey,
+    sk: &BnPlayerSecretKey,
+    deck: &[BnMaskedCardBuf],
+    card_indices: &[usize],
+) -> Vec<BnRevealTokenWithProofBuf> {
+    card_indices.iter()
+        .map(|&i| BnCardProtocol::compute_reveal_token(rng, params, sk, pk, &deck[i].deserialize().unwrap()).unwrap())
+        .map(|token_with_proof| BnRevealTokenWithProofBuf::serialize(token_with_proof).unwrap())
+        .collect()
+}
+
+async fn test_heads_up(
+    players: &[Account],
     contract: &Contract,
+    params: &BnParameters,
 ) -> anyhow::Result<()> {
-    let message: String = user
-        .call( contract.id(), "get_greeting")
-        .args_json(json!({}))
+    let alice = &players[0];
+    let bob = &players[1];
+
+    let mut rng = thread_rng();
+
+    let table = Table::start(&[alice, bob], contract, params, &mut rng).await?;
+    let game_id = table.game_id;
+    call(bob, contract, "derive_initial_deck", json!({ "game_id": game_id })).await?;
+
+    // alice is the dealer, so she shuffles first
+    let deck = table.shuffle_and_deal(&mut rng, params).await?;
+
+    // the blinds go in as soon as everyone's been dealt. Heads-up, the dealer posts the small blind and acts first before the flop
+    let game = get_game(contract, game_id).await?;
+    assert_eq!(game["phase"], "BET0");
+    assert_eq!(game["turn"], 0);
+    assert_eq!(game["balances"], json!(["995", "990"]));
+
+    let out_of_turn = bob.call(contract.id(), "act")
+        .gas(near_units::parse_gas!("300 T") as u64)
+        .args_json(json!({ "game_id": game_id, "action": "check" }))
         .transact()
+        .await?;
+    assert_eq!(error_code(&out_of_turn).as_deref(), Some("NOT_YOUR_TURN"));
+
+    call(alice, contract, "act", json!({ "game_id": game_id, "action": "call" })).await?;
+    call(bob, contract, "act", json!({ "game_id": game_id, "action": "check" })).await?;
+
+    // after the flop, the turn and the river, bob acts first
+    let streets: [(&str, Vec<usize>, [serde_json::Value; 2]); 3] = [
+        ("FLOP", vec![4, 5, 6], [json!("check"), json!("check")]),
+        ("TURN", vec![7], [json!({ "bet": "20" }), json!("call")]),
+        ("RIVER", vec![8], [json!("check"), json!("check")]),
+    ];
+    for (phase, card_indices, [bob_action, alice_action]) in streets {
+        assert_eq!(get_game(contract, game_id).await?["phase"], phase);
+        table.reveal_street(&mut rng, params, &deck, &card_indices).await?;
+
+        assert_eq!(get_game(contract, game_id).await?["turn"], 1);
+        call(bob, contract, "act", json!({ "game_id": game_id, "action": bob_action })).await?;
+        call(alice, contract, "act", json!({ "game_id": game_id, "action": alice_action })).await?;
+    }
+
+    // both players show their hands
+    assert_eq!(get_game(contract, game_id).await?["phase"], "SHOWDOWN_REVEAL");
+    for seat in 0..2 {
+        table.reveal(&mut rng, params, seat, &deck, &[2 * seat, 2 * seat + 1]).await?;
+    }
+
+    // the pot was 60, and the button moves to bob for the next hand
+    let game = get_game(contract, game_id).await?;
+    assert_eq!(game["phase"], "SHUFFLE");
+    assert_eq!(game["dealer"], 1);
+    assert_eq!(game["turn"], 1);
+    let balances: Vec<String> = serde_json::from_value(game["balances"].clone())?;
+    assert!(
+        [["1030", "970"], ["970", "1030"], ["1000", "1000"]].iter().any(|expected| balances == expected),
+        "unexpected balances after showdown: {:?}", balances
+    );
+
+    // bob leaving heads-up leaves alice with all the chips, which ends the game
+    let alice_balance_before = alice.view_account().await?.balance;
+    call(bob, contract, "leave_table", json!({ "game_id": game_id })).await?;
+    let game: Option<serde_json::Value> = contract
+        .view("get_game", json!({ "game_id": game_id }).to_string().into_bytes())
         .await?
         .json()?;
+    assert!(game.is_none(), "a finished game should be deleted");
+    let alice_balance_after = alice.view_account().await?.balance;
+    assert!(alice_balance_after > alice_balance_before + BUY_IN * 9 / 10, "the winner should be paid out when the game finishes");
 
-    assert_eq!(message, "Hello".to_string());
-    println!("      Passed ✅ gets default message");
+    println!("      Passed ✅ plays a heads-up hand");
     Ok(())
 }
 
-async fn test_changes_message(
-    user: &Account,
+/// masks the canonical deck under the aggregate key, along with a masking proof for each card
+fn mask_deck<R: Rng>(rng: &mut R, params: &BnParameters, agg_pk: &BnPublicKey) -> (Vec<BnMaskedCardBuf>, Vec<BnZKProofMaskingBuf>) {
+    get_card_elems_buf(52).unwrap()
+        .into_iter()
+        .map(|c| c.deserialize().unwrap())
+        .map(|c| BnCardProtocol::mask(rng, params, agg_pk, &c, &BnScalar::one()).unwrap())
+        .map(|(card, proof)| (BnMaskedCardBuf::serialize(card).unwrap(), BnZKProofMaskingBuf::serialize(proof).unwrap()))
+        .unzip()
+}
+
+async fn test_seat_management(
+    players: &[Account],
     contract: &Contract,
+    params: &BnParameters,
 ) -> anyhow::Result<()> {
-    user.call(contract.id(), "set_greeting")
-        .args_json(json!({"message": "Howdy"}))
+    let alice = &players[0];
+    let bob = &players[1];
+    let carol = &players[2];
+
+    let mut rng = thread_rng();
+
+    let table = Table::start(&[alice, bob], contract, params, &mut rng).await?;
+    let game_id = table.game_id;
+    let carol_key = game_key(&mut rng, params, carol);
+
+    // with bob sitting out there's nobody for alice to play
+    call(bob, contract, "sit_out", json!({ "game_id": game_id })).await?;
+    let no_hand = alice.call(contract.id(), "derive_initial_deck")
+        .gas(near_units::parse_gas!("300 T") as u64)
+        .args_json(json!({ "game_id": game_id }))
+        .transact()
+        .await?;
+    assert_eq!(error_code(&no_hand).as_deref(), Some("NOT_ENOUGH_PLAYERS"));
+
+    // nobody's shuffled yet, so carol is seated right away
+    let request_seat = || carol.call(contract.id(), "request_seat")
+        .gas(near_units::parse_gas!("300 T") as u64)
+        .deposit(BUY_IN)
+        .args_json(json!({
+            "game_id": game_id,
+            "pk": carol_key.pk_buf,
+            "key_ownership_proof": carol_key.key_proof,
+        }))
+        .transact();
+    request_seat().await?.into_result()?;
+    let again = request_seat().await?;
+    assert_eq!(error_code(&again).as_deref(), Some("ALREADY_JOINED"));
+
+    let game = get_game(contract, game_id).await?;
+    assert_eq!(game["player_account_ids"].as_array().unwrap().len(), 3);
+    assert_eq!(game["dealt_in"], json!([true, false, true]));
+
+    // topping up can't take a stack over the maximum buy-in
+    alice.call(contract.id(), "top_up")
+        .gas(near_units::parse_gas!("300 T") as u64)
+        .deposit(BUY_IN / 2)
+        .args_json(json!({ "game_id": game_id }))
         .transact()
         .await?
         .into_result()?;
+    let over_max = alice.call(contract.id(), "top_up")
+        .gas(near_units::parse_gas!("300 T") as u64)
+        .deposit(BUY_IN)
+        .args_json(json!({ "game_id": game_id }))
+        .transact()
+        .await?;
+    assert_eq!(error_code(&over_max).as_deref(), Some("BUY_IN_OUT_OF_RANGE"));
 
-    let message: String = user
-        .call(contract.id(), "get_greeting")
-        .args_json(json!({}))
+    call(bob, contract, "sit_in", json!({ "game_id": game_id })).await?;
+    let game = get_game(contract, game_id).await?;
+    assert_eq!(game["balances"], json!(["1500", "1000", "1000"]));
+    assert_eq!(game["dealt_in"], json!([true, true, true]));
+
+    // the deck is made for whoever's dealt in, so bob leaving before the shuffle throws it out
+    call(carol, contract, "derive_initial_deck", json!({ "game_id": game_id })).await?;
+    assert_eq!(get_game(contract, game_id).await?["deck_len"], 52);
+    call(bob, contract, "leave_table", json!({ "game_id": game_id })).await?;
+    let game = get_game(contract, game_id).await?;
+    assert_eq!(game["player_account_ids"], json!([alice.id(), carol.id()]));
+    assert_eq!(game["deck_len"], 0);
+
+    // a fresh key for the next hand changes the aggregate key the deck is masked under
+    let agg_pk_before: BnPublicKeyBuf = contract
+        .view("get_aggregate_pubkey", json!({ "game_id": game_id }).to_string().into_bytes())
+        .await?
+        .json()?;
+    let new_key = game_key(&mut rng, params, alice);
+
+    // the proof has to be for the new key
+    let wrong_proof = alice.call(contract.id(), "rotate_key")
+        .gas(near_units::parse_gas!("300 T") as u64)
+        .args_json(json!({ "game_id": game_id, "pk": new_key.pk_buf, "key_ownership_proof": table.keys[0].key_proof }))
         .transact()
+        .await?;
+    assert_eq!(error_code(&wrong_proof).as_deref(), Some("INVALID_PROOF"));
+
+    call(alice, contract, "rotate_key", json!({
+        "game_id": game_id,
+        "pk": new_key.pk_buf,
+        "key_ownership_proof": new_key.key_proof,
+    })).await?;
+    let game = get_game(contract, game_id).await?;
+    assert_eq!(game["player_game_pubkeys"][0], json!(new_key.pk_buf));
+    let agg_pk_after: BnPublicKeyBuf = contract
+        .view("get_aggregate_pubkey", json!({ "game_id": game_id }).to_string().into_bytes())
         .await?
         .json()?;
+    assert_ne!(agg_pk_before.buf, agg_pk_after.buf);
 
-    assert_eq!(message, "Howdy".to_string());
-    println!("      Passed ✅ changes message");
+    println!("      Passed ✅ players can sit out, sit in, top up, rotate keys and take a seat between hands");
     Ok(())
-}
\ No newline at end of file
+}
+
+async fn test_buy_in_and_leave(
+    players: &[Account],
+    contract: &Contract,
+    params: &BnParameters,
+) -> anyhow::Result<()> {
+    let alice = &players[0];
+    let bob = &players[1];
+
+    let mut rng = thread_rng();
+
+    let alice_key = game_key(&mut rng, params, alice);
+    let bob_key = game_key(&mut rng, params, bob);
+
+    // no deposit, no chips
+    let args = json!({ "creator_pk": alice_key.pk_buf, "creator_key_ownership_proof": alice_key.key_proof });
+    assert_eq!(rejection(alice, contract, "create_game", args, 0).await?, "DEPOSIT_TOO_SMALL");
+
+    // the buy-in has to be within the table's range
+    let args = json!({
+        "creator_pk": alice_key.pk_buf,
+        "creator_key_ownership_proof": alice_key.key_proof,
+        "config": { "max_buy_in": "500" },
+    });
+    assert_eq!(rejection(alice, contract, "create_game", args, BUY_IN).await?, "BUY_IN_OUT_OF_RANGE");
+
+    // the small blind can't be bigger than the big blind
+    let args = json!({
+        "creator_pk": alice_key.pk_buf,
+        "creator_key_ownership_proof": alice_key.key_proof,
+        "config": { "small_blind": "50", "big_blind": "20" },
+    });
+    assert_eq!(rejection(alice, contract, "create_game", args, BUY_IN).await?, "INVALID_TABLE_CONFIG");
+
+    let game_id = create_game(alice, contract, &alice_key, json!({})).await?;
+
+    let bob_balance_before = bob.view_account().await?.balance;
+    join_game(bob, contract, game_id, &bob_key).await?;
+
+    let bob_balance_joined = bob.view_account().await?.balance;
+    assert!(bob_balance_joined < bob_balance_before - BUY_IN / 2, "buy-in should be held in escrow");
+
+    bob.call(contract.id(), "leave_table")
+        .gas(near_units::parse_gas!("300 T") as u64)
+        .args_json(json!({ "game_id": game_id }))
+        .transact()
+        .await?
+        .into_result()?;
+
+    // everything but gas comes back
+    let bob_balance_left = bob.view_account().await?.balance;
+    assert!(bob_balance_left > bob_balance_before - parse_near!("0.1 N"), "buy-in should be refunded on leaving");
+
+    // U128 comes back as a string
+    let unclaimed: String = contract
+        .view("get_unclaimed", json!({ "account_id": bob.id() }).to_string().into_bytes())
+        .await?
+        .json()?;
+    assert_eq!(unclaimed, "0");
+
+    // the creator leaving closes the lobby
+    alice.call(contract.id(), "leave_table")
+        .gas(near_units::parse_gas!("300 T") as u64)
+        .args_json(json!({ "game_id": game_id }))
+        .transact()
+        .await?
+        .into_result()?;
+
+    let args = json!({ "game_id": game_id, "pk": bob_key.pk_buf, "key_ownership_proof": bob_key.key_proof });
+    assert_eq!(rejection(bob, contract, "join_game", args, BUY_IN).await?, "GAME_NOT_FOUND");
+
+    println!("      Passed ✅ buy-ins are escrowed and refunded on leaving");
+    Ok(())
+}
+
+/// a player's game key, and their proof that they own it
+struct GameKey {
+    pk: BnPublicKey,
+    sk: BnPlayerSecretKey,
+    pk_buf: BnPublicKeyBuf,
+    key_proof: BnZKProofKeyOwnershipBuf,
+}
+
+fn game_key<R: Rng>(rng: &mut R, params: &BnParameters, player: &Account) -> GameKey {
+    let (pk, sk) = BnCardProtocol::player_keygen(rng, params).unwrap();
+    let key_proof = BnCardProtocol::prove_key_ownership(rng, params, &pk, &sk, player.id().as_bytes()).unwrap();
+    let pk_buf = BnPublicKeyBuf::serialize(pk.clone()).unwrap();
+    GameKey { pk, sk, pk_buf, key_proof: BnZKProofKeyOwnershipBuf::serialize(key_proof).unwrap() }
+}
+
+/// `creator` opens a lobby, buying in for `BUY_IN`. `options` are any other arguments to `create_game`, like `timeouts` or `config`
+async fn create_game(creator: &Account, contract: &Contract, key: &GameKey, options: serde_json::Value) -> anyhow::Result<[u8; 4]> {
+    let mut args = json!({ "creator_pk": key.pk_buf, "creator_key_ownership_proof": key.key_proof });
+    args.as_object_mut().unwrap().extend(options.as_object().unwrap().clone());
+    let game_id = creator.call(contract.id(), "create_game")
+        .gas(near_units::parse_gas!("300 T") as u64)
+        .deposit(BUY_IN)
+        .args_json(args)
+        .transact()
+        .await?
+        .json()?;
+    Ok(game_id)
+}
+
+async fn join_game(player: &Account, contract: &Contract, game_id: [u8; 4], key: &GameKey) -> anyhow::Result<ExecutionSuccess> {
+    let outcome = player.call(contract.id(), "join_game")
+        .gas(near_units::parse_gas!("300 T") as u64)
+        .deposit(BUY_IN)
+        .args_json(json!({ "game_id": game_id, "pk": key.pk_buf, "key_ownership_proof": key.key_proof }))
+        .transact()
+        .await?
+        .into_result()?;
+    Ok(outcome)
+}
+
+/// a game, along with every player's game keys, so a test can do the shuffling and revealing for the whole table
+struct Table<'a> {
+    contract: &'a Contract,
+    game_id: [u8; 4],
+    seats: Vec<&'a Account>,
+    keys: Vec<GameKey>,
+}
+
+impl<'a> Table<'a> {
+    /// `seats[0]` opens a lobby and everyone else joins it, each with a fresh game key. `options` are passed on to `create_game`
+    async fn open<R: Rng>(seats: &[&'a Account], contract: &'a Contract, params: &BnParameters, rng: &mut R, options: serde_json::Value) -> anyhow::Result<Table<'a>> {
+        let keys = seats.iter().map(|player| game_key(rng, params, player)).collect::<Vec<_>>();
+        let game_id = create_game(seats[0], contract, &keys[0], options).await?;
+        for (player, key) in seats.iter().zip(&keys).skip(1) {
+            join_game(player, contract, game_id, key).await?;
+        }
+        Ok(Table { contract, game_id, seats: seats.to_vec(), keys })
+    }
+
+    /// a game with the default options, which `seats` have joined and the creator has started
+    async fn start<R: Rng>(seats: &[&'a Account], contract: &'a Contract, params: &BnParameters, rng: &mut R) -> anyhow::Result<Table<'a>> {
+        let table = Table::open(seats, contract, params, rng, json!({})).await?;
+        table.start_game().await?;
+        Ok(table)
+    }
+
+    async fn start_game(&self) -> anyhow::Result<()> {
+        call(self.seats[0], self.contract, "start_game", json!({ "game_id": self.game_id })).await
+    }
+
+    /// every player shuffles in turn, starting with the dealer, and then deals everyone else their hole cards. Returns the deck the hand is dealt from
+    async fn shuffle_and_deal<R: Rng>(&self, rng: &mut R, params: &BnParameters) -> anyhow::Result<Vec<BnMaskedCardBuf>> {
+        let (contract, game_id) = (self.contract, self.game_id);
+        let agg_pk_buf: BnPublicKeyBuf = contract
+            .view("get_aggregate_pubkey", json!({ "game_id": game_id }).to_string().into_bytes())
+            .await?
+            .json()?;
+        let agg_pk = agg_pk_buf.deserialize().unwrap();
+
+        let dealer = get_game(contract, game_id).await?["dealer"].as_u64().unwrap() as usize;
+        for k in 0..self.seats.len() {
+            let i = (dealer + k) % self.seats.len();
+            assert_eq!(get_game(contract, game_id).await?["turn"], i);
+
+            let deck = get_deck(contract, game_id).await?;
+            let shuffled = shuffle(rng, params, &agg_pk, &deck);
+            call(self.seats[i], contract, "shuffle_deck", json!({ "game_id": game_id, "shuffle": shuffled })).await?;
+        }
+        assert_eq!(get_game(contract, game_id).await?["phase"], "DEAL");
+
+        let deck = get_deck(contract, game_id).await?;
+        for (i, &player) in self.seats.iter().enumerate() {
+            let card_indices = (0..2 * self.seats.len()).filter(|&card| card / 2 != i).collect::<Vec<_>>();
+            let tokens = reveal_tokens(rng, params, &self.keys[i].pk, &self.keys[i].sk, &deck, &card_indices);
+            call(player, contract, "deal", json!({ "game_id": game_id, "card_indices": card_indices, "reveal_tokens_with_proofs": tokens })).await?;
+        }
+        Ok(deck)
+    }
+
+    /// `seat` reveals `card_indices` of `deck`
+    async fn reveal<R: Rng>(&self, rng: &mut R, params: &BnParameters, seat: usize, deck: &[BnMaskedCardBuf], card_indices: &[usize]) -> anyhow::Result<()> {
+        let tokens = reveal_tokens(rng, params, &self.keys[seat].pk, &self.keys[seat].sk, deck, card_indices);
+        let args = json!({ "game_id": self.game_id, "card_indices": card_indices, "reveal_tokens_with_proofs": tokens });
+        call(self.seats[seat], self.contract, "reveal", args).await
+    }
+
+    /// everyone reveals `card_indices` - folded players too, since every dealt in player's key masks the community cards
+    async fn reveal_street<R: Rng>(&self, rng: &mut R, params: &BnParameters, deck: &[BnMaskedCardBuf], card_indices: &[usize]) -> anyhow::Result<()> {
+        for seat in 0..self.seats.len() {
+            self.reveal(rng, params, seat, deck, card_indices).await?;
+        }
+        Ok(())
+    }
+}
+
+fn balances(game: &serde_json::Value) -> Vec<u128> {
+    game["balances"].as_array().unwrap().iter().map(|b| b.as_str().unwrap().parse().unwrap()).collect()
+}
+
+/// four players buy in, the creator masks the deck, and they play two hands: the first goes all the way to showdown,
+/// the second is folded around to the big blind
+async fn test_four_handed(
+    players: &[Account],
+    contract: &Contract,
+    params: &BnParameters,
+) -> anyhow::Result<()> {
+    let seats = [&players[0], &players[1], &players[2], &players[3]];
+    let [alice, bob, carol, dave] = seats;
+
+    let mut rng = thread_rng();
+
+    let table = Table::open(&seats, contract, params, &mut rng, json!({})).await?;
+    let game_id = table.game_id;
+
+    // the lobby is listed while it's waiting to start
+    let lobbies: Vec<serde_json::Value> = contract
+        .view("list_open_lobbies", json!({}).to_string().into_bytes())
+        .await?
+        .json()?;
+    assert!(lobbies.iter().any(|lobby| lobby["id"] == json!(game_id)));
+
+    let lobby: serde_json::Value = contract
+        .view("get_lobby", json!({ "game_id": game_id }).to_string().into_bytes())
+        .await?
+        .json()?;
+    assert_eq!(lobby["player_account_ids"], json!([alice.id(), bob.id(), carol.id(), dave.id()]));
+    assert_eq!(lobby["config"]["big_blind"], json!("10"));
+    assert_eq!(lobby["config"]["betting"], json!("no_limit"));
+
+    table.start_game().await?;
+
+    // and is no longer listed once it starts
+    let lobbies: Vec<serde_json::Value> = contract
+        .view("list_open_lobbies", json!({}).to_string().into_bytes())
+        .await?
+        .json()?;
+    assert!(lobbies.iter().all(|lobby| lobby["id"] != json!(game_id)));
+
+    let games: Vec<[u8; 4]> = contract
+        .view("get_games_for_account", json!({ "account_id": dave.id() }).to_string().into_bytes())
+        .await?
+        .json()?;
+    assert!(games.contains(&game_id));
+
+    let game = get_game(contract, game_id).await?;
+    assert_eq!(game["phase"], "SHUFFLE");
+    assert_eq!(game["player_account_ids"], json!([alice.id(), bob.id(), carol.id(), dave.id()]));
+    assert_eq!(game["balances"], json!(["1000", "1000", "1000", "1000"]));
+    assert_eq!(game["deck_len"], 0);
+
+    let agg_pk_buf: BnPublicKeyBuf = contract
+        .view("get_aggregate_pubkey", json!({ "game_id": game_id }).to_string().into_bytes())
+        .await?
+        .json()?;
+    assert_eq!(game["aggregate_pubkey"], json!(agg_pk_buf));
+    let agg_pk = agg_pk_buf.deserialize().unwrap();
+
+    // alice masks the deck herself. The first batch of masking proofs is checked right away, and anyone can check the rest
+    let (deck_buf, proofs_buf) = mask_deck(&mut rng, params, &agg_pk);
+    let valid: bool = alice.call(contract.id(), "init_deck")
+        .gas(near_units::parse_gas!("300 T") as u64)
+        .args_json(json!({
+            "game_id": game_id,
+            "deck": deck_buf,
+            "masking_proofs": proofs_buf,
+        }))
+        .transact()
+        .await?
+        .json()?;
+    assert!(valid);
+    while get_game(contract, game_id).await?["deck_verified"] != true {
+        call(bob, contract, "verify_initial_deck", json!({ "game_id": game_id })).await?;
+    }
+
+    // first hand: alice has the button, so bob posts the small blind, carol the big blind, and dave acts first
+    let deck = table.shuffle_and_deal(&mut rng, params).await?;
+    let game = get_game(contract, game_id).await?;
+    assert_eq!(game["phase"], "BET0");
+    assert_eq!(game["turn"], 3);
+    assert_eq!(game["balances"], json!(["1000", "995", "990", "1000"]));
+
+    call(dave, contract, "act", json!({ "game_id": game_id, "action": { "raise": { "to": "30" } } })).await?;
+    call(alice, contract, "act", json!({ "game_id": game_id, "action": "fold" })).await?;
+    call(bob, contract, "act", json!({ "game_id": game_id, "action": "call" })).await?;
+    call(carol, contract, "act", json!({ "game_id": game_id, "action": "call" })).await?;
+
+    // the community cards come after everyone's hole cards
+    assert_eq!(get_game(contract, game_id).await?["phase"], "FLOP");
+    table.reveal_street(&mut rng, params, &deck, &[8, 9, 10]).await?;
+
+    // after the flop, the first player left of the button who's still in acts first
+    let game = get_game(contract, game_id).await?;
+    assert_eq!(game["phase"], "BET1");
+    assert_eq!(game["turn"], 1);
+    call(bob, contract, "act", json!({ "game_id": game_id, "action": "check" })).await?;
+    call(carol, contract, "act", json!({ "game_id": game_id, "action": { "bet": "50" } })).await?;
+    call(dave, contract, "act", json!({ "game_id": game_id, "action": "call" })).await?;
+    call(bob, contract, "act", json!({ "game_id": game_id, "action": "fold" })).await?;
+
+    for (phase, card_indices) in [("TURN", [11]), ("RIVER", [12])] {
+        assert_eq!(get_game(contract, game_id).await?["phase"], phase);
+        table.reveal_street(&mut rng, params, &deck, &card_indices).await?;
+
+        assert_eq!(get_game(contract, game_id).await?["turn"], 2);
+        call(carol, contract, "act", json!({ "game_id": game_id, "action": "check" })).await?;
+        call(dave, contract, "act", json!({ "game_id": game_id, "action": "check" })).await?;
+    }
+
+    // only carol and dave show their hands - alice and bob folded
+    let game = get_game(contract, game_id).await?;
+    assert_eq!(game["phase"], "SHOWDOWN_REVEAL");
+    assert_eq!(game["revealed_players"], json!([true, true, false, false]));
+    for seat in [2, 3] {
+        table.reveal(&mut rng, params, seat, &deck, &[2 * seat, 2 * seat + 1]).await?;
+    }
+
+    // the pot was 190, and it's all carol's or dave's, or they split it
+    let game = get_game(contract, game_id).await?;
+    let after_first_hand = balances(&game);
+    assert_eq!(after_first_hand[..2], [1000, 970]);
+    assert!(
+        [[1110, 920], [920, 1110], [1015, 1015]].iter().any(|expected| after_first_hand[2..] == expected[..]),
+        "unexpected balances after showdown: {:?}", after_first_hand
+    );
+
+    // the button moves to bob, and the table is reset for the next hand. Nobody's left, so the deck is kept and just reshuffled
+    assert_eq!(game["phase"], "SHUFFLE");
+    assert_eq!(game["dealer"], 1);
+    assert_eq!(game["turn"], 1);
+    assert_eq!(game["current_bet"], "0");
+    assert_eq!(game["bets"], json!([{ "in": "0" }, { "in": "0" }, { "in": "0" }, { "in": "0" }]));
+    assert_eq!(game["revealed_players"], json!([false, false, false, false]));
+    assert_eq!(game["deck_len"], 52);
+    let tokens: Option<Vec<Option<BnRevealTokenWithProofBuf>>> = contract
+        .view("get_reveal_tokens", json!({ "game_id": game_id, "card_index": 8 }).to_string().into_bytes())
+        .await?
+        .json()?;
+    assert!(tokens.unwrap().iter().all(Option::is_none), "reveal tokens from the last hand should be cleared");
+
+    // second hand: carol posts the small blind, dave the big blind, and it's folded around to him
+    table.shuffle_and_deal(&mut rng, params).await?;
+    let game = get_game(contract, game_id).await?;
+    assert_eq!(game["phase"], "BET0");
+    assert_eq!(game["turn"], 0);
+    for player in [alice, bob, carol] {
+        call(player, contract, "act", json!({ "game_id": game_id, "action": "fold" })).await?;
+    }
+
+    // dave takes carol's small blind, and the button moves on to carol
+    let game = get_game(contract, game_id).await?;
+    assert_eq!(game["phase"], "SHUFFLE");
+    assert_eq!(game["dealer"], 2);
+    assert_eq!(game["turn"], 2);
+    let expected = [after_first_hand[0], after_first_hand[1], after_first_hand[2] - 5, after_first_hand[3] + 5];
+    assert_eq!(balances(&game), expected);
+
+    println!("      Passed ✅ plays two hands four-handed");
+    Ok(())
+}
+
+/// calls `method` as `player`, expecting the contract to refuse. Returns the code of the error it refused with
+async fn rejection(player: &Account, contract: &Contract, method: &str, args: serde_json::Value, deposit: u128) -> anyhow::Result<String> {
+    let outcome = player.call(contract.id(), method)
+        .gas(near_units::parse_gas!("300 T") as u64)
+        .deposit(deposit)
+        .args_json(args)
+        .transact()
+        .await?;
+    assert!(outcome.is_failure(), "{} should have been rejected", method);
+    Ok(error_code(&outcome).expect("the contract should explain why it refused"))
+}
+
+fn deck_bytes(deck: &[BnMaskedCardBuf]) -> Vec<Vec<u8>> {
+    deck.iter().map(|card| card.buf.clone()).collect()
+}
+
+/// players try to cheat at every step of a hand, and the contract turns each attempt down without changing the game.
+/// Between this and the tests above, every proof the contract verifies is shown to hold up the state change it guards
+async fn test_cheating_is_rejected(
+    players: &[Account],
+    contract: &Contract,
+    params: &BnParameters,
+) -> anyhow::Result<()> {
+    let seats = [&players[0], &players[1], &players[2]];
+    let [alice, bob, carol] = seats;
+    let dave = &players[3];
+
+    let mut rng = thread_rng();
+
+    let table = Table::open(&seats, contract, params, &mut rng, json!({})).await?;
+    let game_id = table.game_id;
+    let (alice_key, carol_key) = (&table.keys[0], &table.keys[2]);
+
+    // key ownership proofs are bound to the account that made them, so dave can't pass off alice's key as his own
+    let lobbies_before: Vec<serde_json::Value> = contract
+        .view("list_open_lobbies", json!({}).to_string().into_bytes())
+        .await?
+        .json()?;
+    let args = json!({ "creator_pk": alice_key.pk_buf, "creator_key_ownership_proof": alice_key.key_proof });
+    assert_eq!(rejection(dave, contract, "create_game", args, BUY_IN).await?, "INVALID_PROOF");
+    let lobbies_after: Vec<serde_json::Value> = contract
+        .view("list_open_lobbies", json!({}).to_string().into_bytes())
+        .await?
+        .json()?;
+    assert_eq!(lobbies_before.len(), lobbies_after.len());
+
+    // or join with carol's
+    let args = json!({ "game_id": game_id, "pk": carol_key.pk_buf, "key_ownership_proof": carol_key.key_proof });
+    assert_eq!(rejection(dave, contract, "join_game", args, BUY_IN).await?, "INVALID_PROOF");
+    let lobby: serde_json::Value = contract
+        .view("get_lobby", json!({ "game_id": game_id }).to_string().into_bytes())
+        .await?
+        .json()?;
+    assert_eq!(lobby["player_account_ids"], json!([alice.id(), bob.id(), carol.id()]));
+
+    // only the creator can start the game
+    assert_eq!(rejection(bob, contract, "start_game", json!({ "game_id": game_id }), 0).await?, "NOT_CREATOR");
+    assert!(get_game(contract, game_id).await?.is_null());
+    table.start_game().await?;
+
+    // a seat or a new key needs a proof made by the account asking for it, too
+    let args = json!({ "game_id": game_id, "pk": alice_key.pk_buf, "key_ownership_proof": alice_key.key_proof });
+    assert_eq!(rejection(dave, contract, "request_seat", args, BUY_IN).await?, "INVALID_PROOF");
+    let args = json!({ "game_id": game_id, "pk": carol_key.pk_buf, "key_ownership_proof": carol_key.key_proof });
+    assert_eq!(rejection(bob, contract, "rotate_key", args, 0).await?, "INVALID_PROOF");
+    let game = get_game(contract, game_id).await?;
+    assert_eq!(game["pending_seats"], json!([]));
+    assert_eq!(game["player_game_pubkeys"], json!(table.keys.iter().map(|key| &key.pk_buf).collect::<Vec<_>>()));
+
+    let agg_pk_buf: BnPublicKeyBuf = contract
+        .view("get_aggregate_pubkey", json!({ "game_id": game_id }).to_string().into_bytes())
+        .await?
+        .json()?;
+    let agg_pk = agg_pk_buf.deserialize().unwrap();
+
+    // only the creator can submit the initial deck
+    let (deck_buf, proofs_buf) = mask_deck(&mut rng, params, &agg_pk);
+    let args = json!({ "game_id": game_id, "deck": deck_buf, "masking_proofs": proofs_buf });
+    assert_eq!(rejection(bob, contract, "init_deck", args, 0).await?, "NOT_CREATOR");
+    assert_eq!(get_game(contract, game_id).await?["deck_len"], 0);
+
+    // alice sneaks a second copy of a card into the last batch of masking proofs, which init_deck doesn't check itself
+    let (mut deck_buf, mut proofs_buf) = mask_deck(&mut rng, params, &agg_pk);
+    deck_buf[51] = deck_buf[50].clone();
+    proofs_buf[51] = proofs_buf[50].clone();
+    let valid: bool = alice.call(contract.id(), "init_deck")
+        .gas(near_units::parse_gas!("300 T") as u64)
+        .args_json(json!({ "game_id": game_id, "deck": deck_buf, "masking_proofs": proofs_buf }))
+        .transact()
+        .await?
+        .json()?;
+    assert!(valid, "the first batch of masking proofs is fine");
+
+    // nobody can shuffle the deck until every proof has been checked, and checking them catches the duplicate
+    let shuffled = shuffle(&mut rng, params, &agg_pk, &get_deck(contract, game_id).await?);
+    let args = json!({ "game_id": game_id, "shuffle": shuffled });
+    assert_eq!(rejection(alice, contract, "shuffle_deck", args, 0).await?, "DECK_NOT_VERIFIED");
+    let mut batches = Vec::new();
+    loop {
+        let valid: bool = bob.call(contract.id(), "verify_initial_deck")
+            .gas(near_units::parse_gas!("300 T") as u64)
+            .args_json(json!({ "game_id": game_id }))
+            .transact()
+            .await?
+            .json()?;
+        batches.push(valid);
+        if !valid || get_game(contract, game_id).await?["deck_verified"] == true {
+            break;
+        }
+    }
+    assert_eq!(batches, [true, true, false]);
+    assert_eq!(get_game(contract, game_id).await?["deck_len"], 0);
+
+    call(bob, contract, "derive_initial_deck", json!({ "game_id": game_id })).await?;
+    let deck = get_deck(contract, game_id).await?;
+
+    // alice deals, so she shuffles first. Bob can't jump the queue
+    let shuffled = shuffle(&mut rng, params, &agg_pk, &deck);
+    let args = json!({ "game_id": game_id, "shuffle": shuffled });
+    assert_eq!(rejection(bob, contract, "shuffle_deck", args, 0).await?, "NOT_YOUR_TURN");
+
+    // a shuffle whose proof is for some other shuffle of the deck - say, one that puts the cards where alice wants them
+    let (cards, _) = shuffle(&mut rng, params, &agg_pk, &deck).deserialize().unwrap();
+    let (_, proof) = shuffle(&mut rng, params, &agg_pk, &deck).deserialize().unwrap();
+    let forged = BnShuffleOutputBuf::serialize((cards, proof)).unwrap();
+    let args = json!({ "game_id": game_id, "shuffle": forged });
+    assert_eq!(rejection(alice, contract, "shuffle_deck", args, 0).await?, "INVALID_PROOF");
+    assert_eq!(deck_bytes(&get_deck(contract, game_id).await?), deck_bytes(&deck));
+    assert_eq!(get_game(contract, game_id).await?["turn"], 0);
+
+    // alice shuffles for real. Bob can't just replay her shuffle - it's a proof about the deck before hers
+    let shuffled = shuffle(&mut rng, params, &agg_pk, &deck);
+    call(alice, contract, "shuffle_deck", json!({ "game_id": game_id, "shuffle": shuffled })).await?;
+    let args = json!({ "game_id": game_id, "shuffle": shuffled });
+    assert_eq!(rejection(bob, contract, "shuffle_deck", args, 0).await?, "INVALID_PROOF");
+    assert_eq!(get_game(contract, game_id).await?["turn"], 1);
+
+    for player in [bob, carol] {
+        let shuffled = shuffle(&mut rng, params, &agg_pk, &get_deck(contract, game_id).await?);
+        call(player, contract, "shuffle_deck", json!({ "game_id": game_id, "shuffle": shuffled })).await?;
+    }
+    let deck = get_deck(contract, game_id).await?;
+    assert_eq!(get_game(contract, game_id).await?["phase"], "DEAL");
+
+    // bob deals with carol's key instead of his own
+    let card_indices = [0, 1, 4, 5];
+    let tokens = reveal_tokens(&mut rng, params, &table.keys[2].pk, &table.keys[2].sk, &deck, &card_indices);
+    let args = json!({ "game_id": game_id, "card_indices": card_indices, "reveal_tokens_with_proofs": tokens });
+    assert_eq!(rejection(bob, contract, "deal", args, 0).await?, "INVALID_PROOF");
+    assert_eq!(get_game(contract, game_id).await?["revealed_players"], json!([false, false, false]));
+
+    // and nobody deals twice
+    let card_indices = [2, 3, 4, 5];
+    let tokens = reveal_tokens(&mut rng, params, &table.keys[0].pk, &table.keys[0].sk, &deck, &card_indices);
+    let args = json!({ "game_id": game_id, "card_indices": card_indices, "reveal_tokens_with_proofs": tokens });
+    call(alice, contract, "deal", args.clone()).await?;
+    assert_eq!(rejection(alice, contract, "deal", args, 0).await?, "ALREADY_REVEALED");
+    for (i, &player) in seats.iter().enumerate().skip(1) {
+        let card_indices = (0..6).filter(|&card| card / 2 != i).collect::<Vec<_>>();
+        let tokens = reveal_tokens(&mut rng, params, &table.keys[i].pk, &table.keys[i].sk, &deck, &card_indices);
+        call(player, contract, "deal", json!({ "game_id": game_id, "card_indices": card_indices, "reveal_tokens_with_proofs": tokens })).await?;
+    }
+
+    // three-handed, the button acts first before the flop. The flop can't be revealed before the betting's done
+    let game = get_game(contract, game_id).await?;
+    assert_eq!(game["phase"], "BET0");
+    assert_eq!(game["turn"], 0);
+    assert_eq!(rejection(bob, contract, "act", json!({ "game_id": game_id, "action": "call" }), 0).await?, "NOT_YOUR_TURN");
+    let card_indices = [6, 7, 8];
+    let tokens = reveal_tokens(&mut rng, params, &table.keys[1].pk, &table.keys[1].sk, &deck, &card_indices);
+    let args = json!({ "game_id": game_id, "card_indices": card_indices, "reveal_tokens_with_proofs": tokens });
+    assert_eq!(rejection(bob, contract, "reveal", args, 0).await?, "WRONG_PHASE");
+    let game = get_game(contract, game_id).await?;
+    assert_eq!(game["phase"], "BET0");
+    assert_eq!(game["turn"], 0);
+
+    call(alice, contract, "act", json!({ "game_id": game_id, "action": "call" })).await?;
+    call(bob, contract, "act", json!({ "game_id": game_id, "action": "call" })).await?;
+    call(carol, contract, "act", json!({ "game_id": game_id, "action": "check" })).await?;
+    assert_eq!(get_game(contract, game_id).await?["phase"], "FLOP");
+
+    // and nobody bets until the flop's revealed
+    assert_eq!(rejection(alice, contract, "act", json!({ "game_id": game_id, "action": "check" }), 0).await?, "WRONG_PHASE");
+
+    // carol reveals the flop with bob's key, then tries to reveal alice's hole cards instead
+    let tokens = reveal_tokens(&mut rng, params, &table.keys[1].pk, &table.keys[1].sk, &deck, &card_indices);
+    let args = json!({ "game_id": game_id, "card_indices": card_indices, "reveal_tokens_with_proofs": tokens });
+    assert_eq!(rejection(carol, contract, "reveal", args, 0).await?, "INVALID_PROOF");
+    let tokens = reveal_tokens(&mut rng, params, &table.keys[2].pk, &table.keys[2].sk, &deck, &[0, 1]);
+    let args = json!({ "game_id": game_id, "card_indices": [0, 1], "reveal_tokens_with_proofs": tokens });
+    assert_eq!(rejection(carol, contract, "reveal", args, 0).await?, "CARD_INDICES");
+
+    let tokens: Option<Vec<Option<BnRevealTokenWithProofBuf>>> = contract
+        .view("get_reveal_tokens", json!({ "game_id": game_id, "card_index": 6 }).to_string().into_bytes())
+        .await?
+        .json()?;
+    assert!(tokens.unwrap().iter().all(Option::is_none), "rejected reveal tokens shouldn't be stored");
+    assert_eq!(get_game(contract, game_id).await?["revealed_players"], json!([false, false, false]));
+
+    // her real tokens go through once, and only once
+    table.reveal(&mut rng, params, 2, &deck, &card_indices).await?;
+    let tokens = reveal_tokens(&mut rng, params, &table.keys[2].pk, &table.keys[2].sk, &deck, &card_indices);
+    let args = json!({ "game_id": game_id, "card_indices": card_indices, "reveal_tokens_with_proofs": tokens });
+    assert_eq!(rejection(carol, contract, "reveal", args, 0).await?, "ALREADY_REVEALED");
+    assert_eq!(get_game(contract, game_id).await?["revealed_players"], json!([false, false, true]));
+
+    println!("      Passed ✅ rejects cheating");
+    Ok(())
+}